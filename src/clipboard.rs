@@ -0,0 +1,196 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use crate::item::TodoItem;
+
+/// Where copied items go besides the clipboard inside todo
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ClipboardProvider {
+    /// only inside todo, lost when it exits
+    Internal,
+    /// also the system clipboard, through a copy command or OSC 52 escape sequences
+    System,
+}
+
+impl FromStr for ClipboardProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "internal" => Ok(ClipboardProvider::Internal),
+            "system" => Ok(ClipboardProvider::System),
+            _ => Err(format!("unknown clipboard {}, expected internal or system", s)),
+        }
+    }
+}
+
+/// The items copied with `y`, optionally kept in sync with the system clipboard
+pub struct Clipboard {
+    items: Vec<TodoItem>,
+    provider: ClipboardProvider,
+    /// shell command that copies its stdin to the system clipboard
+    copy_command: Option<String>,
+    /// shell command that prints the system clipboard
+    paste_command: Option<String>,
+    /// text last put on the system clipboard, pasting it keeps the metadata of the copied items
+    copied_text: String,
+}
+
+impl Clipboard {
+    pub fn new(provider: ClipboardProvider, copy_command: Option<String>, paste_command: Option<String>) -> Self {
+        Self {
+            items: vec![],
+            provider,
+            copy_command,
+            paste_command,
+            copied_text: String::new(),
+        }
+    }
+
+    /// copy items, putting their text on the system clipboard one per line
+    /// OSC 52 is used unless a copy command overrides it, and stays as a fallback if the command fails
+    pub fn copy(&mut self, items: Vec<TodoItem>, stdout: &mut io::Stdout) -> io::Result<()> {
+        self.items = items;
+        if self.provider == ClipboardProvider::Internal {
+            return Ok(());
+        }
+        self.copied_text = items_to_text(&self.items);
+        let overridden = self
+            .copy_command
+            .as_ref()
+            .is_some_and(|command| run_copy_command(command, &self.copied_text).is_ok());
+        if !overridden {
+            // the terminal puts this on the clipboard of the machine it runs on, even over ssh
+            write!(stdout, "\x1b]52;c;{}\x07", base64(self.copied_text.as_bytes()))?;
+            stdout.flush()?;
+        }
+        Ok(())
+    }
+
+    /// the items to paste, read from the system clipboard when there is a paste command
+    /// OSC 52 can't be read back by most terminals, so without one this is what todo copied last,
+    /// which is also the fallback if the command fails
+    pub fn paste(&self) -> Vec<TodoItem> {
+        let command = match (&self.paste_command, self.provider) {
+            (Some(command), ClipboardProvider::System) => command,
+            _ => return self.items.clone(),
+        };
+        match run_paste_command(command) {
+            Ok(text) if text.trim_end() != self.copied_text.trim_end() => text_to_items(&text),
+            _ => self.items.clone(),
+        }
+    }
+}
+
+/// one item per line with subtasks indented
+pub fn items_to_text(items: &[TodoItem]) -> String {
+    items
+        .iter()
+        .map(|item| format!("{}{}", " ".repeat(item.depth * crate::INDENT_WIDTH), item.text))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// new items from text with one item per line, indented lines become subtasks
+pub fn text_to_items(text: &str) -> Vec<TodoItem> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let text = line.trim_start();
+            let indent = line.len() - text.len();
+            TodoItem {
+                depth: indent / crate::INDENT_WIDTH,
+                ..TodoItem::new(text.trim_end().to_string())
+            }
+        })
+        .collect()
+}
+
+fn run_copy_command(command: &str, text: &str) -> io::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{} failed", command)))
+    }
+}
+
+fn run_paste_command(command: &str) -> io::Result<String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(io::Error::other(format!("{} failed", command)))
+    }
+}
+
+/// standard base64 with padding, as OSC 52 expects
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut res = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xfb, 0xff, 0xfe]), "+//+");
+        assert_eq!(base64("é".as_bytes()), "w6k=");
+    }
+
+    #[test]
+    fn text_round_trips_with_indentation() {
+        let items: Vec<TodoItem> = [("plan trip", 0), ("book hotel", 1), ("pack  bags", 2), ("call mum", 0)]
+            .iter()
+            .map(|&(text, depth)| TodoItem { depth, ..TodoItem::new(text.to_string()) })
+            .collect();
+        let text = items_to_text(&items);
+        let indent = " ".repeat(crate::INDENT_WIDTH);
+        assert_eq!(text, format!("plan trip\n{0}book hotel\n{0}{0}pack  bags\ncall mum", indent));
+        let pasted = text_to_items(&text);
+        let outline: Vec<(&str, usize)> = pasted.iter().map(|item| (item.text.as_str(), item.depth)).collect();
+        assert_eq!(outline, [("plan trip", 0), ("book hotel", 1), ("pack  bags", 2), ("call mum", 0)]);
+    }
+
+    #[test]
+    fn pasted_text_skips_blank_lines_and_trailing_spaces() {
+        let indent = " ".repeat(crate::INDENT_WIDTH);
+        let pasted = text_to_items(&format!("a  \n\n{0}  \n{0}b\r\n", indent));
+        let outline: Vec<(&str, usize)> = pasted.iter().map(|item| (item.text.as_str(), item.depth)).collect();
+        assert_eq!(outline, [("a", 0), ("b", 1)]);
+    }
+}
@@ -0,0 +1,237 @@
+use structopt::StructOpt;
+use crate::sort::{Sort, SortKey};
+use crate::{matches, List, NamedList};
+
+// Changes to a list made from the command line, done the same way as the keys in the interactive view
+// items are given by their number, counting from 1 in the order `--print` shows them,
+// or by text they contain, which has to match a single item. numbers past the end of the list are matched as text.
+// items inside collapsed ones are expanded so they can be worked on
+// not a doc comment since structopt would use it as the help text of the whole program
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Check off an item and its subtasks
+    Done {
+        item: String,
+    },
+    /// Move a checked off item and its subtasks back to the todo list
+    Undo {
+        item: String,
+    },
+    /// Delete an item and its subtasks
+    Rm {
+        item: String,
+        /// Delete from the done list
+        #[structopt(long)]
+        done: bool,
+    },
+    /// Replace the text of an item, keeping its metadata
+    Edit {
+        item: String,
+        #[structopt(required = true, min_values = 1)]
+        text: Vec<String>,
+        /// Edit an item in the done list
+        #[structopt(long)]
+        done: bool,
+    },
+    /// Move an item and its subtasks to another position among the items at its level
+    Mv {
+        item: String,
+        to: usize,
+        /// Move an item in the done list
+        #[structopt(long)]
+        done: bool,
+    },
+    /// Sort a list by text, ignorecase, natural, length, created, completed, due or priority
+    Sort {
+        #[structopt(default_value = "priority")]
+        key: SortKey,
+        #[structopt(short, long)]
+        reverse: bool,
+        /// Sort the done list
+        #[structopt(long)]
+        done: bool,
+    },
+}
+
+impl Command {
+    /// apply the command to `named_list`, returning a message explaining what went wrong if it can't be
+    pub fn run(&self, named_list: &mut NamedList) -> Result<(), String> {
+        match self {
+            Command::Done { item } => {
+                select(&mut named_list.todo, item)?;
+                named_list.check_item();
+            }
+            Command::Undo { item } => {
+                select(&mut named_list.done, item)?;
+                named_list.uncheck_item();
+            }
+            Command::Rm { item, done } => {
+                let list = pick(named_list, *done);
+                select(list, item)?;
+                list.remove();
+            }
+            Command::Edit { item, text, done } => {
+                let text = text.join(" ");
+                if text.trim().is_empty() {
+                    return Err("The new text can't be empty, use rm to delete an item".to_string());
+                }
+                let list = pick(named_list, *done);
+                select(list, item)?;
+                list.set_current_text(text);
+            }
+            Command::Mv { item, to, done } => {
+                let list = pick(named_list, *done);
+                select(list, item)?;
+                move_to(list, to.saturating_sub(1));
+            }
+            Command::Sort { key, reverse, done } => pick(named_list, *done).sort(Sort { key: *key, reverse: *reverse }),
+        }
+        Ok(())
+    }
+}
+
+fn pick(named_list: &mut NamedList, done: bool) -> &mut List {
+    if done {
+        &mut named_list.done
+    } else {
+        &mut named_list.todo
+    }
+}
+
+/// put the cursor on the item given by its number or text it contains, expanding whatever it is hidden in.
+/// numbers past the end of the list are looked for in the text so items like `2026 taxes` can be picked too
+fn select(list: &mut List, item: &str) -> Result<(), String> {
+    let len = list.items.len();
+    let index = match item.parse::<usize>() {
+        Ok(number) if number >= 1 && number <= len => number - 1,
+        number => {
+            let found: Vec<usize> = (0..len).filter(|&i| matches(&list.items[i].text, item)).collect();
+            match (&found[..], number) {
+                ([index], _) => *index,
+                ([], Ok(number)) => return Err(format!("There is no item {}, the list has {} items", number, len)),
+                ([], Err(_)) => return Err(format!("No item contains {}", item)),
+                _ => {
+                    let found: Vec<String> = found
+                        .iter()
+                        .map(|&i| format!("  {} {}", i + 1, list.items[i].text))
+                        .collect();
+                    return Err(format!("{} items contain {}:\n{}", found.len(), item, found.join("\n")));
+                }
+            }
+        }
+    };
+    list.expand_to(index);
+    list.current_index = index;
+    Ok(())
+}
+
+/// drag the current item the way `J` and `K` do until it reaches `target`
+/// items can only pass whole subtrees, so it stops at the first position at or past `target`
+fn move_to(list: &mut List, target: usize) {
+    loop {
+        let index = list.current_index;
+        let items = list.items.clone();
+        if index > target {
+            list.shift_up();
+        } else if index < target {
+            list.shift_down();
+        } else {
+            break;
+        }
+        let moved_up = list.current_index < index;
+        let moved_down = list.current_index > index;
+        // dragging past the top or bottom of the list wraps around, which isn't wanted here
+        if !(moved_up && index > target || moved_down && index < target) {
+            list.items = items;
+            list.current_index = index;
+            break;
+        }
+        if moved_up && list.current_index <= target || moved_down && list.current_index >= target {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::TodoItem;
+    use crate::ListType;
+
+    fn list_of(items: &[(&str, usize)]) -> List {
+        let items = items
+            .iter()
+            .map(|&(text, depth)| TodoItem { depth, ..TodoItem::from_text(text.to_string()) })
+            .collect();
+        List::new(items, ListType::Todo)
+    }
+
+    fn texts(list: &List) -> Vec<&str> {
+        list.items.iter().map(|item| item.text.as_str()).collect()
+    }
+
+    #[test]
+    fn items_are_selected_by_number_or_text() {
+        let mut list = list_of(&[("milk", 0), ("bread", 0), ("2026 taxes", 0)]);
+        select(&mut list, "2").unwrap();
+        assert_eq!(list.current_index, 1);
+        select(&mut list, "MILK").unwrap();
+        assert_eq!(list.current_index, 0);
+        // too big to be a number in the list, so it's looked for in the text
+        select(&mut list, "2026").unwrap();
+        assert_eq!(list.current_index, 2);
+        assert!(select(&mut list, "4").unwrap_err().contains("no item 4"));
+        assert!(select(&mut list, "9").unwrap_err().contains("no item 9"));
+        assert!(select(&mut list, "eggs").unwrap_err().contains("No item"));
+    }
+
+    #[test]
+    fn ambiguous_text_lists_the_matches() {
+        let mut list = list_of(&[("buy milk", 0), ("buy bread", 0), ("call mum", 0)]);
+        list.current_index = 2;
+        let error = select(&mut list, "buy").unwrap_err();
+        assert!(error.starts_with("2 items contain buy"));
+        assert!(error.contains("  1 buy milk") && error.contains("  2 buy bread"));
+        assert_eq!(list.current_index, 2);
+    }
+
+    #[test]
+    fn hidden_items_are_expanded() {
+        let mut list = list_of(&[("a", 0), ("a1", 1), ("a1x", 2), ("b", 0)]);
+        list.items[0].collapsed = true;
+        list.items[1].collapsed = true;
+        select(&mut list, "a1x").unwrap();
+        assert_eq!(list.current_index, 2);
+        assert!(list.has_current());
+        assert!(!list.items[0].collapsed && !list.items[1].collapsed);
+    }
+
+    #[test]
+    fn moving_stops_at_the_target() {
+        let mut list = list_of(&[("a", 0), ("b", 0), ("b1", 1), ("c", 0)]);
+        select(&mut list, "c").unwrap();
+        move_to(&mut list, 0);
+        assert_eq!(texts(&list), ["c", "a", "b", "b1"]);
+        assert_eq!(list.current_index, 0);
+        // b can only pass c as a whole, so it ends up at the first position past the target
+        select(&mut list, "a").unwrap();
+        move_to(&mut list, 2);
+        assert_eq!(texts(&list), ["c", "b", "b1", "a"]);
+        assert_eq!(list.current_index, 3);
+    }
+
+    #[test]
+    fn moving_past_the_ends_does_not_wrap_around() {
+        let mut list = list_of(&[("a", 0), ("b", 0), ("c", 0)]);
+        select(&mut list, "b").unwrap();
+        move_to(&mut list, 9);
+        assert_eq!(texts(&list), ["a", "c", "b"]);
+        assert_eq!(list.current_index, 2);
+
+        let mut list = list_of(&[("a", 0), ("a1", 1), ("a2", 1), ("b", 0)]);
+        select(&mut list, "a1").unwrap();
+        move_to(&mut list, 3);
+        assert_eq!(texts(&list), ["a", "a2", "a1", "b"]);
+        assert_eq!(list.current_index, 2);
+    }
+}
@@ -0,0 +1,356 @@
+use std::fmt;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+/// furthest a relative date can be from today, enough to reach any year written with four digits
+const MAX_OFFSET_DAYS: i64 = 10_000 * 366;
+/// largest number of units in a `rec:` extension, more than enough for a recurrence in any unit
+const MAX_RECURRENCE_COUNT: i64 = 1000;
+
+/// A calendar date in the local timezone
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Self> {
+        if month == 0 || month > 12 || day == 0 || day > days_in_month(year, month) {
+            None
+        } else {
+            Some(Self { year, month, day })
+        }
+    }
+
+    pub fn today() -> Self {
+        Self::from_timestamp(crate::item::now())
+    }
+
+    /// the local date of a time in seconds since the unix epoch
+    pub fn from_timestamp(timestamp: u64) -> Self {
+        let timestamp = timestamp as i64;
+        let local = timestamp + local_offset(timestamp);
+        Self::from_days(local.div_euclid(SECONDS_PER_DAY))
+    }
+
+    /// local midnight at the start of this date in seconds since the unix epoch
+    pub fn to_timestamp(self) -> u64 {
+        let local = self.to_days() * SECONDS_PER_DAY;
+        (local - local_offset(local)).max(0) as u64
+    }
+
+    /// the date `days` days after the unix epoch
+    pub fn from_days(days: i64) -> Self {
+        // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400) as i32 + (month <= 2) as i32;
+        Self { year, month, day }
+    }
+
+    /// number of days since the unix epoch
+    pub fn to_days(self) -> i64 {
+        // https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+        let year = self.year as i64 - (self.month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let mp = (self.month as i64 + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    pub fn add_days(self, days: i64) -> Self {
+        Self::from_days(self.to_days() + days)
+    }
+
+    /// add days, or None if the date would be out of the years `YYYY-MM-DD` can hold
+    pub fn checked_add_days(self, days: i64) -> Option<Self> {
+        if days.abs() > MAX_OFFSET_DAYS {
+            return None;
+        }
+        self.add_days(days).four_digit_year()
+    }
+
+    /// add months like `add_months`, or None if the date would be out of the years `YYYY-MM-DD` can hold
+    pub fn checked_add_months(self, months: i64) -> Option<Self> {
+        if months.abs() > MAX_OFFSET_DAYS / 28 {
+            return None;
+        }
+        self.add_months(months).four_digit_year()
+    }
+
+    fn four_digit_year(self) -> Option<Self> {
+        (0..=9999).contains(&self.year).then_some(self)
+    }
+
+    /// add months, clamping the day to the end of the month so jan 31 + 1 month is feb 28 or 29
+    pub fn add_months(self, months: i64) -> Self {
+        let month_index = self.year as i64 * 12 + (self.month as i64 - 1) + months;
+        let year = month_index.div_euclid(12) as i32;
+        let month = month_index.rem_euclid(12) as u32 + 1;
+        Self {
+            year,
+            month,
+            day: self.day.min(days_in_month(year, month)),
+        }
+    }
+
+    /// day of the week, 0 is monday and 6 is sunday
+    pub fn weekday(self) -> u32 {
+        // the unix epoch was a thursday
+        (self.to_days() + 3).rem_euclid(7) as u32
+    }
+
+    /// parse a date relative to `today`
+    /// accepts `YYYY-MM-DD`, `today`, `tomorrow`, `yesterday`, day names like `friday` or `fri`
+    /// meaning the next such day, and offsets like `3d`, `+2w`, `1m` or `1y`.
+    /// dates that would fall outside of four digit years are rejected
+    pub fn parse_relative(s: &str, today: Self) -> Option<Self> {
+        if let Some(date) = Self::parse(s) {
+            return Some(date);
+        }
+        let s = s.to_ascii_lowercase();
+        match s.as_str() {
+            "today" | "tod" => return Some(today),
+            "tomorrow" | "tom" => return today.checked_add_days(1),
+            "yesterday" => return today.checked_add_days(-1),
+            _ => {}
+        }
+        if let Some(weekday) = parse_weekday(&s) {
+            let days = (weekday + 7 - today.weekday()) % 7;
+            return today.checked_add_days(if days == 0 { 7 } else { days as i64 });
+        }
+        let (count, unit) = parse_offset(s.strip_prefix('+').unwrap_or(&s))?;
+        match unit {
+            'd' => today.checked_add_days(count),
+            'w' => today.checked_add_days(count.checked_mul(7)?),
+            'm' => today.checked_add_months(count),
+            'y' => today.checked_add_months(count.checked_mul(12)?),
+            _ => None,
+        }
+    }
+
+    /// parse a date written as `YYYY-MM-DD`
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.splitn(3, '-');
+        let year = parts.next()?;
+        let month = parts.next()?;
+        let day = parts.next()?;
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+        if !(year.chars().chain(month.chars()).chain(day.chars()).all(|ch| ch.is_ascii_digit())) {
+            return None;
+        }
+        Self::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// How often a recurring item comes back
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interval {
+    Days(i64),
+    Months(i64),
+    /// every monday to friday
+    Weekdays,
+}
+
+/// When a recurring item comes back after it's completed, from a `rec:` extension such as `rec:1w`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Recurrence {
+    pub interval: Interval,
+    /// count from the due date instead of the day the item was completed, written with a leading `+`
+    pub strict: bool,
+}
+
+impl Recurrence {
+    /// parse `Nd`, `Nw`, `Nm`, `Ny`, `daily`, `weekly`, `monthly`, `yearly` or `weekdays`,
+    /// optionally with a leading `+`. N goes from 1 to `MAX_RECURRENCE_COUNT`
+    pub fn parse(s: &str) -> Option<Self> {
+        let (strict, s) = match s.strip_prefix('+') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let interval = match s.to_ascii_lowercase().as_str() {
+            "daily" => Interval::Days(1),
+            "weekly" => Interval::Days(7),
+            "monthly" => Interval::Months(1),
+            "yearly" => Interval::Months(12),
+            "weekdays" => Interval::Weekdays,
+            s => match parse_offset(s)? {
+                (count, _) if !(1..=MAX_RECURRENCE_COUNT).contains(&count) => return None,
+                (count, 'd') => Interval::Days(count),
+                (count, 'w') => Interval::Days(count.checked_mul(7)?),
+                (count, 'm') => Interval::Months(count),
+                (count, 'y') => Interval::Months(count.checked_mul(12)?),
+                _ => return None,
+            },
+        };
+        Some(Self { interval, strict })
+    }
+
+    /// the first date the item comes back on after `date`
+    pub fn next(self, date: Date) -> Date {
+        match self.interval {
+            Interval::Days(days) => date.add_days(days),
+            Interval::Months(months) => date.add_months(months),
+            Interval::Weekdays => {
+                let mut next = date.add_days(1);
+                while next.weekday() >= 5 {
+                    next = next.add_days(1);
+                }
+                next
+            }
+        }
+    }
+}
+
+/// split a count followed by a single letter unit, like `3d`
+fn parse_offset(s: &str) -> Option<(i64, char)> {
+    let unit = s.chars().last()?;
+    let count = s[..s.len() - unit.len_utf8()].parse().ok()?;
+    Some((count, unit))
+}
+
+/// day of the week from its name or a prefix of at least three letters, 0 is monday
+fn parse_weekday(s: &str) -> Option<u32> {
+    const NAMES: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+    if s.len() < 3 {
+        return None;
+    }
+    NAMES.iter().position(|name| name.starts_with(s)).map(|i| i as u32)
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// offset of the local timezone from UTC in seconds at the given time
+#[cfg(unix)]
+fn local_offset(timestamp: i64) -> i64 {
+    let time = timestamp as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        0
+    } else {
+        tm.tm_gmtoff as i64
+    }
+}
+
+#[cfg(not(unix))]
+fn local_offset(_timestamp: i64) -> i64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> Date {
+        Date::parse(s).unwrap()
+    }
+
+    #[test]
+    fn relative_dates() {
+        // a wednesday
+        let today = date("2024-01-31");
+        let parse = |s| Date::parse_relative(s, today).map(|date| date.to_string());
+        assert_eq!(parse("2025-06-01").as_deref(), Some("2025-06-01"));
+        assert_eq!(parse("today").as_deref(), Some("2024-01-31"));
+        assert_eq!(parse("Tomorrow").as_deref(), Some("2024-02-01"));
+        assert_eq!(parse("yesterday").as_deref(), Some("2024-01-30"));
+        assert_eq!(parse("fri").as_deref(), Some("2024-02-02"));
+        // the same day of the week is a week away
+        assert_eq!(parse("wednesday").as_deref(), Some("2024-02-07"));
+        assert_eq!(parse("3d").as_deref(), Some("2024-02-03"));
+        assert_eq!(parse("-1d").as_deref(), Some("2024-01-30"));
+        assert_eq!(parse("+2w").as_deref(), Some("2024-02-14"));
+        assert_eq!(parse("1m").as_deref(), Some("2024-02-29"));
+        assert_eq!(parse("1y").as_deref(), Some("2025-01-31"));
+        assert_eq!(parse("soon"), None);
+        assert_eq!(parse("we"), None);
+        assert_eq!(parse("3x"), None);
+    }
+
+    #[test]
+    fn relative_dates_stay_in_four_digit_years() {
+        let today = date("2024-01-31");
+        for s in ["999999999999999999y", "9223372036854775807d", "-9223372036854775808w", "1537228672809129301m", "8000y", "-2025y"] {
+            assert_eq!(Date::parse_relative(s, today), None, "{}", s);
+        }
+        assert_eq!(Date::parse_relative("7975y", today), Some(date("9999-01-31")));
+        assert_eq!(Date::parse_relative("-2024y", today), Some(date("0000-01-31")));
+        assert_eq!(Date::parse_relative("tomorrow", date("9999-12-31")), None);
+    }
+
+    #[test]
+    fn add_months_clamps_the_day() {
+        assert_eq!(date("2024-01-31").add_months(1), date("2024-02-29"));
+        assert_eq!(date("2023-01-31").add_months(1), date("2023-02-28"));
+        assert_eq!(date("2024-03-31").add_months(-1), date("2024-02-29"));
+        assert_eq!(date("2024-05-31").add_months(1), date("2024-06-30"));
+        assert_eq!(date("2024-02-29").add_months(12), date("2025-02-28"));
+        assert_eq!(date("2024-11-15").add_months(3), date("2025-02-15"));
+        assert_eq!(date("2024-01-15").add_months(-13), date("2022-12-15"));
+    }
+
+    #[test]
+    fn recurrence() {
+        let rec = |s| Recurrence::parse(s).map(|rec| (rec.interval, rec.strict));
+        assert_eq!(rec("daily"), Some((Interval::Days(1), false)));
+        assert_eq!(rec("+2w"), Some((Interval::Days(14), true)));
+        assert_eq!(rec("3m"), Some((Interval::Months(3), false)));
+        assert_eq!(rec("+1y"), Some((Interval::Months(12), true)));
+        assert_eq!(rec("Weekdays"), Some((Interval::Weekdays, false)));
+        for s in ["0d", "-1w", "1000000000000000000w", "999999999999999999y", "1001d", "2x", "w"] {
+            assert_eq!(rec(s), None, "{}", s);
+        }
+        let next = |s, from| Recurrence::parse(s).unwrap().next(date(from));
+        assert_eq!(next("1m", "2024-01-31"), date("2024-02-29"));
+        assert_eq!(next("1y", "2024-02-29"), date("2025-02-28"));
+        assert_eq!(next("+1w", "2024-12-30"), date("2025-01-06"));
+    }
+
+    #[test]
+    fn weekday_recurrence_skips_weekends() {
+        let weekdays = Recurrence::parse("weekdays").unwrap();
+        // thursday, friday, saturday and sunday
+        assert_eq!(weekdays.next(date("2024-02-01")), date("2024-02-02"));
+        assert_eq!(weekdays.next(date("2024-02-02")), date("2024-02-05"));
+        assert_eq!(weekdays.next(date("2024-02-03")), date("2024-02-05"));
+        assert_eq!(weekdays.next(date("2024-02-04")), date("2024-02-05"));
+        assert_eq!(date("2024-02-05").weekday(), 0);
+    }
+
+    #[test]
+    fn days_round_trip() {
+        assert_eq!(Date::from_days(0), date("1970-01-01"));
+        assert_eq!(date("1970-01-01").weekday(), 3);
+        for days in [-719_468, -1, 0, 59, 11_016, 19_782, 2_932_896] {
+            assert_eq!(Date::from_days(days).to_days(), days);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use crate::clipboard::{items_to_text, text_to_items};
+use crate::item::TodoItem;
+
+/// the user's editor from `$VISUAL` or `$EDITOR`, falling back to vi
+fn editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// create a file in the temporary directory that only the user can read, with a random name ending in `name`
+/// the file must not exist already so nothing else can have it open or have put a link there
+fn create_temp_file(name: &str) -> io::Result<(PathBuf, fs::File)> {
+    loop {
+        // std seeds the hashers of hash maps with random keys, which is enough to make the name unpredictable
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        let path = env::temp_dir().join(format!("todo_rs-{:016x}-{}", hasher.finish(), name));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// let the user edit `text` in their editor, in a temporary file ending in `name`
+/// returns what was saved, or None if the editor failed so the edit should be thrown away
+pub fn edit_text(text: &str, name: &str) -> io::Result<Option<String>> {
+    let (path, mut file) = create_temp_file(name)?;
+    let written = file.write_all(text.as_bytes());
+    drop(file);
+    if let Err(err) = written {
+        let _ = fs::remove_file(&path);
+        return Err(err);
+    }
+    // run through the shell so editors given with arguments like `code --wait` work
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor()))
+        .arg("sh")
+        .arg(&path)
+        .status();
+    let res = match status {
+        Ok(status) if status.success() => fs::read_to_string(&path).map(Some),
+        Ok(_) => Ok(None),
+        Err(err) => Err(err),
+    };
+    let _ = fs::remove_file(&path);
+    res
+}
+
+/// let the user edit items in their editor as text, one per line with subtasks indented
+/// items whose text wasn't changed keep their metadata
+pub fn edit_items(items: &[TodoItem]) -> io::Result<Option<Vec<TodoItem>>> {
+    let mut text = items_to_text(items);
+    text.push('\n');
+    Ok(edit_text(&text, "list.txt")?.map(|text| keep_metadata(items, text_to_items(&text))))
+}
+
+/// replace edited items with the original items that have the same text, moved to their new depth
+fn keep_metadata(old: &[TodoItem], new: Vec<TodoItem>) -> Vec<TodoItem> {
+    let mut unused: Vec<&TodoItem> = old.iter().collect();
+    new.into_iter()
+        .map(|item| match unused.iter().position(|old| old.text == item.text) {
+            Some(index) => TodoItem {
+                depth: item.depth,
+                ..unused.remove(index).clone()
+            },
+            None => item,
+        })
+        .collect()
+}
+
+/// the items an edit removed and the ones it added, matching items by their text
+pub fn diff_items<'a>(old: &'a [TodoItem], new: &'a [TodoItem]) -> (Vec<&'a TodoItem>, Vec<&'a TodoItem>) {
+    let mut added: Vec<&TodoItem> = new.iter().collect();
+    let mut removed = vec![];
+    for item in old {
+        match added.iter().position(|new| new.text == item.text) {
+            Some(index) => {
+                added.remove(index);
+            }
+            None => removed.push(item),
+        }
+    }
+    (removed, added)
+}
@@ -0,0 +1,86 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::storage::{write_atomic, ListLock};
+
+/// how many entries are kept in the history file
+const HISTORY_SIZE: usize = 1000;
+
+/// Which inputs share a history, so searches aren't offered when adding items
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HistoryKind {
+    Item,
+    List,
+    Search,
+    Filter,
+}
+
+impl HistoryKind {
+    fn name(self) -> &'static str {
+        match self {
+            HistoryKind::Item => "item",
+            HistoryKind::List => "list",
+            HistoryKind::Search => "search",
+            HistoryKind::Filter => "filter",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [HistoryKind::Item, HistoryKind::List, HistoryKind::Search, HistoryKind::Filter]
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+}
+
+/// Strings entered in insert mode, oldest first, saved to a file one per line after the kind of input
+pub struct History {
+    path: PathBuf,
+    entries: Vec<(HistoryKind, String)>,
+}
+
+impl History {
+    pub fn load(path: PathBuf) -> Self {
+        let entries = read_entries(&path);
+        Self { path, entries }
+    }
+
+    /// the entries entered in inputs of `kind`, oldest first
+    pub fn entries(&self, kind: HistoryKind) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|(entry_kind, _)| *entry_kind == kind)
+            .map(|(_, text)| text.clone())
+            .collect()
+    }
+
+    /// add an entry and save it, moving it to the end if it was entered before
+    /// the file is read again first so entries from other instances of todo are kept
+    pub fn add(&mut self, kind: HistoryKind, text: &str) -> io::Result<()> {
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+        let _lock = ListLock::acquire(&self.path)?;
+        self.entries = read_entries(&self.path);
+        self.entries.retain(|(entry_kind, entry)| !(*entry_kind == kind && entry == text));
+        self.entries.push((kind, text.to_string()));
+        let excess = self.entries.len().saturating_sub(HISTORY_SIZE);
+        self.entries.drain(..excess);
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|(kind, text)| format!("{}\t{}\n", kind.name(), text))
+            .collect();
+        write_atomic(&self.path, contents.as_bytes())
+    }
+}
+
+fn read_entries(path: &Path) -> Vec<(HistoryKind, String)> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let (kind, text) = line.split_once('\t')?;
+            Some((HistoryKind::from_name(kind)?, text.to_string()))
+        })
+        .collect()
+}
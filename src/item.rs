@@ -0,0 +1,671 @@
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::date::{Date, Recurrence};
+
+/// first line of a file written in the current on-disk format
+pub const FORMAT_HEADER: &str = "# todo_rs v1";
+const FORMAT_HEADER_PREFIX: &str = "# todo_rs v";
+const FORMAT_VERSION: u32 = 1;
+/// put before the todo.txt keys for metadata todo.txt has no place for, so `note:` and the like typed into an item stay text
+const TODO_TXT_KEY_PREFIX: &str = "todo_rs-";
+/// priorities go from `A` down to this, which is the fifth level
+pub const LOWEST_PRIORITY: char = 'E';
+
+/// seconds since the unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// How a list file is written, and how files without the versioned header are read
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StorageFormat {
+    /// versioned format with a header line and tab separated metadata
+    Native,
+    /// the todo.txt convention, see https://github.com/todotxt/todo.txt
+    TodoTxt,
+}
+
+impl FromStr for StorageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "native" => Ok(StorageFormat::Native),
+            "todotxt" | "todo.txt" => Ok(StorageFormat::TodoTxt),
+            _ => Err(format!("unknown storage format {}, expected native or todotxt", s)),
+        }
+    }
+}
+
+/// The kind of a word in an item's text, following the todo.txt convention
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WordKind {
+    Plain,
+    /// `+project`
+    Project,
+    /// `@context`
+    Context,
+    /// `#tag`
+    Hashtag,
+    /// `key:value`
+    KeyValue,
+}
+
+impl WordKind {
+    pub fn of(word: &str) -> Self {
+        let tagged = |prefix: char| word.len() > 1 && word.starts_with(prefix);
+        if tagged('+') {
+            WordKind::Project
+        } else if tagged('@') {
+            WordKind::Context
+        } else if tagged('#') {
+            WordKind::Hashtag
+        } else if key_value(word).is_some() {
+            WordKind::KeyValue
+        } else {
+            WordKind::Plain
+        }
+    }
+}
+
+/// split a `key:value` word, neither side may be empty or contain another colon
+pub fn key_value(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once(':')?;
+    if key.is_empty() || value.is_empty() || value.contains(':') || word.contains(char::is_whitespace) {
+        None
+    } else {
+        Some((key, value))
+    }
+}
+
+/// How soon an item with a due date is due
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DueStatus {
+    Overdue,
+    Today,
+    Later,
+}
+
+/// replace relative dates in `due:` extensions, such as `due:tomorrow`, with the date they refer to
+pub fn normalize_dates(text: &str, today: Date) -> String {
+    text.split(' ')
+        .map(|word| match key_value(word) {
+            Some(("due", value)) => match Date::parse_relative(value, today) {
+                Some(date) => format!("due:{}", date),
+                None => word.to_string(),
+            },
+            _ => word.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A single entry in a list along with its metadata
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TodoItem {
+    pub text: String,
+    /// time the item was created in seconds since the unix epoch
+    pub created: Option<u64>,
+    /// time the item was completed in seconds since the unix epoch
+    pub completed: Option<u64>,
+    pub priority: Option<char>,
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+    /// how deeply the item is nested, an item is a subtask of the closest item above it with a smaller depth
+    pub depth: usize,
+    /// whether the item's subtasks are hidden
+    pub collapsed: bool,
+}
+
+impl TodoItem {
+    /// a newly created item, stamped with the current time
+    pub fn new(text: String) -> Self {
+        let mut item = Self {
+            created: Some(now()),
+            ..Self::default()
+        };
+        item.set_text(&text);
+        item
+    }
+
+    /// set the text as typed by the user, filling in relative dates
+    pub fn set_text(&mut self, text: &str) {
+        self.text = normalize_dates(text, Date::today());
+    }
+
+    /// the value of the first `key:value` extension in the text with the given key
+    pub fn extension(&self, key: &str) -> Option<&str> {
+        self.text
+            .split(' ')
+            .filter_map(key_value)
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// the `+project`, `@context` and `#tag` words in the text followed by the tags in the metadata,
+    /// which are given a `#` if they don't start with one of those already
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = vec![];
+        let words = self.text
+            .split(' ')
+            .filter(|word| matches!(WordKind::of(word), WordKind::Project | WordKind::Context | WordKind::Hashtag))
+            .map(str::to_string);
+        let metadata = self.tags.iter().map(|tag| match WordKind::of(tag) {
+            WordKind::Project | WordKind::Context | WordKind::Hashtag => tag.clone(),
+            _ => format!("#{}", tag),
+        });
+        for tag in words.chain(metadata) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+
+    /// true if the item has `tag`, which matches a tag starting with any of `+`, `@` or `#` when given without one
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let sigil = matches!(WordKind::of(tag), WordKind::Project | WordKind::Context | WordKind::Hashtag);
+        self.tags().iter().any(|t| t == tag || (!sigil && &t[1..] == tag))
+    }
+
+    /// replace the value of the first `key:value` extension in the text with the given key,
+    /// adding one to the end if there isn't one
+    pub fn set_extension(&mut self, key: &str, value: &str) {
+        let extension = format!("{}:{}", key, value);
+        let mut found = false;
+        let words: Vec<&str> = self.text
+            .split(' ')
+            .map(|word| match key_value(word) {
+                Some((k, _)) if k == key && !found => {
+                    found = true;
+                    extension.as_str()
+                }
+                _ => word,
+            })
+            .collect();
+        self.text = if found {
+            words.join(" ")
+        } else if self.text.is_empty() {
+            extension
+        } else {
+            format!("{} {}", self.text, extension)
+        };
+    }
+
+    /// the `rec:` extension
+    pub fn recurrence(&self) -> Option<Recurrence> {
+        self.extension("rec").and_then(Recurrence::parse)
+    }
+
+    /// a fresh copy of a recurring item that is due the next time it comes back after being completed `today`
+    pub fn next_occurrence(&self, today: Date) -> Option<Self> {
+        let recurrence = self.recurrence()?;
+        let from = match self.due() {
+            Some(due) if recurrence.strict => due,
+            _ => today,
+        };
+        let mut item = TodoItem {
+            created: Some(now()),
+            completed: None,
+            collapsed: false,
+            ..self.clone()
+        };
+        item.set_extension("due", &recurrence.next(from).to_string());
+        Some(item)
+    }
+
+    /// the date in the `due:` extension
+    pub fn due(&self) -> Option<Date> {
+        self.extension("due").and_then(Date::parse)
+    }
+
+    pub fn due_status(&self, today: Date) -> Option<DueStatus> {
+        self.due().map(|due| match due.cmp(&today) {
+            std::cmp::Ordering::Less => DueStatus::Overdue,
+            std::cmp::Ordering::Equal => DueStatus::Today,
+            std::cmp::Ordering::Greater => DueStatus::Later,
+        })
+    }
+
+    /// an item with no metadata
+    pub fn from_text(text: String) -> Self {
+        Self {
+            text,
+            ..Self::default()
+        }
+    }
+
+    /// raise the priority one level, items without one start at the lowest level
+    pub fn raise_priority(&mut self) {
+        self.priority = match self.priority {
+            None => Some(LOWEST_PRIORITY),
+            Some('A') => Some('A'),
+            Some(priority) => Some((priority as u8 - 1) as char),
+        };
+    }
+
+    /// lower the priority one level, lowering it past the lowest level removes it
+    pub fn lower_priority(&mut self) {
+        self.priority = match self.priority {
+            Some(priority) if priority < LOWEST_PRIORITY => Some((priority as u8 + 1) as char),
+            _ => None,
+        };
+    }
+
+    pub fn complete(&mut self) {
+        self.completed = Some(now());
+    }
+
+    pub fn uncomplete(&mut self) {
+        self.completed = None;
+    }
+
+    /// serialize to a single line in the todo.txt convention
+    /// metadata without a todo.txt equivalent is written as `key:value` extensions
+    pub fn to_todo_txt(&self) -> String {
+        let mut parts = vec![];
+        if let Some(completed) = self.completed {
+            parts.push(format!("x {}", Date::from_timestamp(completed)));
+        } else if let Some(priority) = self.priority {
+            parts.push(format!("({})", priority));
+        }
+        if let Some(created) = self.created {
+            parts.push(Date::from_timestamp(created).to_string());
+        }
+        if !self.text.is_empty() {
+            parts.push(self.text.clone());
+        }
+        if let (Some(_), Some(priority)) = (self.completed, self.priority) {
+            parts.push(format!("pri:{}", priority));
+        }
+        for tag in &self.tags {
+            parts.push(format!("{}tag:{}", TODO_TXT_KEY_PREFIX, percent_encode(tag)));
+        }
+        if let Some(note) = &self.note {
+            parts.push(format!("{}note:{}", TODO_TXT_KEY_PREFIX, percent_encode(note)));
+        }
+        if self.depth > 0 {
+            parts.push(format!("{}depth:{}", TODO_TXT_KEY_PREFIX, self.depth));
+        }
+        if self.collapsed {
+            parts.push(format!("{}collapsed:1", TODO_TXT_KEY_PREFIX));
+        }
+        parts.join(" ")
+    }
+
+    /// parse a single line in the todo.txt convention
+    /// a line with none of the todo.txt markers is read as plain text unchanged
+    pub fn from_todo_txt(line: &str) -> Self {
+        let mut item = Self::default();
+        let mut rest = line;
+        if let Some((date, after)) = rest.strip_prefix("x ").and_then(split_date) {
+            item.completed = Some(date.to_timestamp());
+            rest = after;
+        } else if let Some(priority) = parse_priority(rest) {
+            item.priority = Some(priority);
+            rest = &rest[4..];
+        }
+        if let Some((date, after)) = split_date(rest) {
+            item.created = Some(date.to_timestamp());
+            rest = after;
+        }
+        let mut special = false;
+        let words: Vec<&str> = rest
+            .split(' ')
+            .filter(|word| {
+                let (key, value) = match key_value(word) {
+                    Some(key_value) => key_value,
+                    None => return true,
+                };
+                match (key.strip_prefix(TODO_TXT_KEY_PREFIX), value) {
+                    _ if key == "pri" && item.completed.is_some() => item.priority = value.chars().next(),
+                    (Some("tag"), _) => item.tags.push(percent_decode(value)),
+                    (Some("note"), _) => item.note = Some(percent_decode(value)),
+                    (Some("depth"), _) => match value.parse() {
+                        Ok(depth) => item.depth = depth,
+                        Err(_) => return true,
+                    },
+                    (Some("collapsed"), "1") => item.collapsed = true,
+                    _ => return true,
+                }
+                special = true;
+                false
+            })
+            .collect();
+        // only rebuild the text when something was taken out of it so spacing is otherwise kept as is
+        item.text = if special {
+            words.into_iter().filter(|word| !word.is_empty()).collect::<Vec<_>>().join(" ")
+        } else {
+            rest.to_string()
+        };
+        item
+    }
+
+    /// the item as it is read back after being saved in `format`
+    pub fn as_saved(&self, format: StorageFormat) -> Self {
+        match format {
+            StorageFormat::Native => self.clone(),
+            // todo.txt only keeps the dates of the times
+            StorageFormat::TodoTxt => Self::from_todo_txt(&self.to_todo_txt()),
+        }
+    }
+
+    /// serialize to a single line of the current on-disk format
+    /// the text comes first followed by tab separated `key=value` fields
+    pub fn to_line(&self) -> String {
+        let mut line = escape(&self.text);
+        let mut field = |key: &str, value: &str| {
+            line.push('\t');
+            line.push_str(key);
+            line.push('=');
+            line.push_str(&escape(value));
+        };
+        if let Some(created) = self.created {
+            field("created", &created.to_string());
+        }
+        if let Some(completed) = self.completed {
+            field("completed", &completed.to_string());
+        }
+        if let Some(priority) = self.priority {
+            field("priority", &priority.to_string());
+        }
+        for tag in &self.tags {
+            field("tag", tag);
+        }
+        if let Some(note) = &self.note {
+            field("note", note);
+        }
+        if self.depth > 0 {
+            field("depth", &self.depth.to_string());
+        }
+        if self.collapsed {
+            field("collapsed", "1");
+        }
+        line
+    }
+
+    /// parse a single line of the current on-disk format, unknown fields are ignored
+    pub fn from_line(line: &str) -> Self {
+        let mut fields = line.split('\t');
+        let mut item = Self::from_text(unescape(fields.next().unwrap_or("")));
+        for field in fields {
+            let (key, value) = match field.split_once('=') {
+                Some((key, value)) => (key, unescape(value)),
+                None => continue,
+            };
+            match key {
+                "created" => item.created = value.parse().ok(),
+                "completed" => item.completed = value.parse().ok(),
+                "priority" => item.priority = value.chars().next().filter(char::is_ascii_uppercase),
+                "tag" => item.tags.push(value),
+                "note" => item.note = Some(value),
+                "depth" => item.depth = value.parse().unwrap_or(0),
+                "collapsed" => item.collapsed = value == "1",
+                _ => {}
+            }
+        }
+        item
+    }
+}
+
+/// get the format version declared by a header line, or None if the line is not a header
+pub fn format_version(line: &str) -> Option<u32> {
+    line.strip_prefix(FORMAT_HEADER_PREFIX)?.trim().parse().ok()
+}
+
+/// parse the contents of a list file saved in `format`
+/// files without a header are plain text with one item per line, and are only read as todo.txt
+/// when lists are stored that way so text that happens to look like todo.txt markers is kept as is.
+/// fails for files written by a newer version of todo, which can't be read without losing something
+pub fn parse_items(contents: &str, format: StorageFormat) -> Result<Vec<TodoItem>, String> {
+    let mut lines = contents.lines();
+    let mut items: Vec<TodoItem> = match contents.lines().next().and_then(format_version) {
+        Some(version) => {
+            if version > FORMAT_VERSION {
+                return Err(format!("format version {} is newer than supported version {}", version, FORMAT_VERSION));
+            }
+            lines.next(); // skip header
+            lines
+                .filter(|line| !line.is_empty())
+                .map(TodoItem::from_line)
+                .collect()
+        }
+        None if format == StorageFormat::TodoTxt => lines.map(TodoItem::from_todo_txt).collect(),
+        None => lines.map(|line| TodoItem::from_text(line.to_string())).collect(),
+    };
+    // an item can be at most one level deeper than the one before it, which also keeps edited files from
+    // giving depths too big to indent by
+    let mut max_depth = 0;
+    for item in &mut items {
+        item.depth = item.depth.min(max_depth);
+        max_depth = item.depth + 1;
+    }
+    Ok(items)
+}
+
+/// serialize a list to the contents of a file in the given format
+pub fn serialize_items(items: &[TodoItem], format: StorageFormat) -> String {
+    let mut contents = String::new();
+    if format == StorageFormat::Native {
+        contents.push_str(FORMAT_HEADER);
+        contents.push('\n');
+    }
+    for item in items {
+        contents.push_str(&match format {
+            StorageFormat::Native => item.to_line(),
+            StorageFormat::TodoTxt => item.to_todo_txt(),
+        });
+        contents.push('\n');
+    }
+    contents
+}
+
+/// parse a `(A) ` priority marker at the start of a line
+fn parse_priority(s: &str) -> Option<char> {
+    let bytes = s.as_bytes();
+    if bytes.len() >= 4 && bytes[0] == b'(' && bytes[1].is_ascii_uppercase() && bytes[2] == b')' && bytes[3] == b' ' {
+        Some(bytes[1] as char)
+    } else {
+        None
+    }
+}
+
+/// split a leading `YYYY-MM-DD` date off of a string
+fn split_date(s: &str) -> Option<(Date, &str)> {
+    let (date, rest) = s.split_once(' ').unwrap_or((s, ""));
+    Date::parse(date).map(|date| (date, rest))
+}
+
+/// encode characters that can't appear in a todo.txt `key:value` extension
+fn percent_encode(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '%' | ':' | ' ' | '\t' | '\n' | '\r' => res.push_str(&format!("%{:02X}", ch as u32)),
+            _ => res.push(ch),
+        }
+    }
+    res
+}
+
+fn percent_decode(s: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = s.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        let decoded = if byte == b'%' && after.len() >= 2 {
+            std::str::from_utf8(&after[..2]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match decoded {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &after[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = after;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '\\' => res.push_str("\\\\"),
+            '\t' => res.push_str("\\t"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            _ => res.push(ch),
+        }
+    }
+    res
+}
+
+fn unescape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            res.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => res.push('\t'),
+            Some('n') => res.push('\n'),
+            Some('r') => res.push('\r'),
+            Some(other) => res.push(other),
+            None => res.push('\\'),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// an item with every kind of metadata, with times at midnight so they survive todo.txt dates
+    fn full_item() -> TodoItem {
+        TodoItem {
+            text: "call mom +family @phone due:2024-03-05".to_string(),
+            created: Some(Date::parse("2024-03-01").unwrap().to_timestamp()),
+            completed: Some(Date::parse("2024-03-04").unwrap().to_timestamp()),
+            priority: Some('B'),
+            tags: vec!["with space".to_string(), "a:b%c".to_string()],
+            note: Some("first line\nsecond\tline: 100%".to_string()),
+            depth: 1,
+            collapsed: true,
+        }
+    }
+
+    #[test]
+    fn native_round_trip() {
+        let items = vec![
+            TodoItem::from_text("parent".to_string()),
+            full_item(),
+            TodoItem::from_text("back\\slash \\t not a tab\twith a tab\r\n".to_string()),
+            TodoItem::from_text(String::new()),
+        ];
+        let contents = serialize_items(&items, StorageFormat::Native);
+        assert!(contents.starts_with(FORMAT_HEADER));
+        assert_eq!(contents.lines().count(), items.len() + 1);
+        // the empty item is dropped like blank lines are
+        assert_eq!(parse_items(&contents, StorageFormat::Native).unwrap(), items[..3]);
+        assert_eq!(parse_items(&contents, StorageFormat::TodoTxt).unwrap(), items[..3]);
+    }
+
+    #[test]
+    fn todo_txt_round_trip() {
+        let items = vec![
+            TodoItem::from_text("parent".to_string()),
+            full_item(),
+            TodoItem {
+                completed: None,
+                ..full_item()
+            },
+            TodoItem::from_text("plain   spacing kept".to_string()),
+        ];
+        let contents = serialize_items(&items, StorageFormat::TodoTxt);
+        assert_eq!(
+            contents.lines().nth(1).unwrap(),
+            "x 2024-03-04 2024-03-01 call mom +family @phone due:2024-03-05 pri:B todo_rs-tag:with%20space \
+             todo_rs-tag:a%3Ab%25c todo_rs-note:first%20line%0Asecond%09line%3A%20100%25 todo_rs-depth:1 todo_rs-collapsed:1",
+        );
+        assert!(contents.lines().nth(2).unwrap().starts_with("(B) 2024-03-01 call mom"));
+        assert_eq!(parse_items(&contents, StorageFormat::TodoTxt).unwrap(), items);
+    }
+
+    #[test]
+    fn todo_txt_keys_written_by_others_stay_in_the_text() {
+        for line in ["call re: note:important", "depth:3 dive tag:x", "collapsed:1 bridge", "(A) pri:B from elsewhere"] {
+            let item = TodoItem::from_todo_txt(line);
+            assert!(line.ends_with(&item.text));
+            assert_eq!((item.depth, item.collapsed, item.note, item.tags), (0, false, None, vec![]));
+        }
+        let done = TodoItem::from_todo_txt("x 2024-03-04 paid rent pri:A");
+        assert_eq!((done.text.as_str(), done.priority), ("paid rent", Some('A')));
+    }
+
+    #[test]
+    fn headerless_files_are_plain_text_unless_stored_as_todo_txt() {
+        let contents = "x 2024-03-04 paid rent\n(A) todo_rs-depth:1 todo_rs-note:hi\n";
+        let plain = parse_items(contents, StorageFormat::Native).unwrap();
+        assert_eq!(
+            plain,
+            vec![
+                TodoItem::from_text("x 2024-03-04 paid rent".to_string()),
+                TodoItem::from_text("(A) todo_rs-depth:1 todo_rs-note:hi".to_string()),
+            ]
+        );
+        let todo_txt = parse_items(contents, StorageFormat::TodoTxt).unwrap();
+        assert_eq!(todo_txt[0].text, "paid rent");
+        assert!(todo_txt[0].completed.is_some());
+        assert_eq!(todo_txt[1].priority, Some('A'));
+        assert_eq!(todo_txt[1].depth, 1);
+        assert_eq!(todo_txt[1].note.as_deref(), Some("hi"));
+    }
+
+    #[test]
+    fn depths_are_limited_to_one_below_the_item_before() {
+        let depths = |contents: &str, format| -> Vec<usize> {
+            parse_items(contents, format).unwrap().iter().map(|item| item.depth).collect()
+        };
+        let native = "# todo_rs v1\na\tdepth=9223372036854775807\nb\nc\tdepth=1\nd\tdepth=5\ne\tdepth=2\n";
+        assert_eq!(depths(native, StorageFormat::Native), [0, 0, 1, 2, 2]);
+        let todo_txt = "a todo_rs-depth:400000000000\nb\nc todo_rs-depth:1\nd todo_rs-depth:18446744073709551616\n";
+        assert_eq!(depths(todo_txt, StorageFormat::TodoTxt), [0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn newer_format_version_is_an_error() {
+        assert!(parse_items("# todo_rs v2\nfoo\n", StorageFormat::Native).is_err());
+        assert!(parse_items("# todo_rs v1\nfoo\n", StorageFormat::Native).is_ok());
+    }
+
+    #[test]
+    fn percent_encoding() {
+        for s in ["", "plain", "a b:c%d\te\nf\rg", "%zz %4", "café 🎉"] {
+            assert_eq!(percent_decode(&percent_encode(s)), s);
+        }
+        assert_eq!(percent_encode("50% off: now"), "50%25%20off%3A%20now");
+        // a percent sign that doesn't start an escape is kept
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%4g"), "%4g");
+    }
+
+    #[test]
+    fn escaping() {
+        for s in ["", "plain", "a\\b\tc\nd\re", "\\t", "trailing\\"] {
+            assert_eq!(unescape(&escape(s)), s);
+        }
+        assert_eq!(escape("a\\b\tc"), "a\\\\b\\tc");
+        assert_eq!(unescape("trailing\\"), "trailing\\");
+    }
+}
@@ -0,0 +1,509 @@
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use crate::{next_grapheme, prev_grapheme};
+
+/// how many killed strings are remembered
+const KILL_RING_SIZE: usize = 16;
+
+/// What the last edit was, so kills can be joined together and yanks cycled
+#[derive(Clone, Debug, PartialEq)]
+enum LastEdit {
+    Other,
+    Kill,
+    /// the yanked text and which kill ring entry it came from
+    Yank(Range<usize>, usize),
+    /// the history entry shown and the text typed before browsing, which entries have to start with
+    History(usize, String),
+    /// where the completed word starts, the completions and the one shown.
+    /// the last completion is the word as it was typed
+    Complete(usize, Vec<String>, usize),
+}
+
+/// The single line of text typed in insert mode, with emacs style editing
+pub struct LineEditor {
+    text: String,
+    /// byte index of the cursor in `text`
+    cursor: usize,
+    /// byte index of the first grapheme drawn when the text is wider than the screen
+    scroll: usize,
+    /// killed text, most recent last. kept between inputs so it can be yanked into the next one
+    kill_ring: Vec<String>,
+    last_edit: LastEdit,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+            cursor: 0,
+            scroll: 0,
+            kill_ring: vec![],
+            last_edit: LastEdit::Other,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// replace the text, putting the cursor at the start or the end of it
+    pub fn set(&mut self, text: String, at_end: bool) {
+        self.cursor = if at_end { text.len() } else { 0 };
+        self.text = text;
+        self.scroll = 0;
+        self.last_edit = LastEdit::Other;
+    }
+
+    /// take the text, leaving the editor empty
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        self.scroll = 0;
+        self.last_edit = LastEdit::Other;
+        std::mem::take(&mut self.text)
+    }
+
+    pub fn insert(&mut self, ch: char) {
+        self.text.insert(self.cursor, ch);
+        self.cursor += ch.len_utf8();
+        self.last_edit = LastEdit::Other;
+    }
+
+    pub fn left(&mut self) {
+        self.move_to(prev_grapheme(&self.text, self.cursor));
+    }
+
+    pub fn right(&mut self) {
+        self.move_to(next_grapheme(&self.text, self.cursor));
+    }
+
+    pub fn home(&mut self) {
+        self.move_to(0);
+    }
+
+    pub fn end(&mut self) {
+        self.move_to(self.text.len());
+    }
+
+    pub fn word_left(&mut self) {
+        self.move_to(self.word_start());
+    }
+
+    pub fn word_right(&mut self) {
+        self.move_to(self.word_end());
+    }
+
+    pub fn backspace(&mut self) {
+        let start = prev_grapheme(&self.text, self.cursor);
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+        self.last_edit = LastEdit::Other;
+    }
+
+    pub fn delete(&mut self) {
+        let end = next_grapheme(&self.text, self.cursor);
+        self.text.replace_range(self.cursor..end, "");
+        self.last_edit = LastEdit::Other;
+    }
+
+    /// kill back to the previous whitespace, like Ctrl-w in a shell
+    pub fn kill_whitespace_word_back(&mut self) {
+        let before = self.text[..self.cursor].trim_end();
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        self.kill(start..self.cursor);
+    }
+
+    pub fn kill_word_back(&mut self) {
+        self.kill(self.word_start()..self.cursor);
+    }
+
+    pub fn kill_word_forward(&mut self) {
+        self.kill(self.cursor..self.word_end());
+    }
+
+    pub fn kill_to_start(&mut self) {
+        self.kill(0..self.cursor);
+    }
+
+    pub fn kill_to_end(&mut self) {
+        self.kill(self.cursor..self.text.len());
+    }
+
+    /// insert the most recently killed text
+    pub fn yank(&mut self) {
+        if let Some(index) = self.kill_ring.len().checked_sub(1) {
+            self.yank_entry(index);
+        }
+    }
+
+    /// replace the text just yanked with the kill before it, cycling through the kill ring
+    pub fn yank_pop(&mut self) {
+        if let LastEdit::Yank(range, index) = self.last_edit.clone() {
+            self.text.replace_range(range.clone(), "");
+            self.cursor = range.start;
+            let index = index.checked_sub(1).unwrap_or(self.kill_ring.len() - 1);
+            self.yank_entry(index);
+        }
+    }
+
+    /// replace the text with an older or newer entry of `history` that starts with what was typed before browsing,
+    /// going past the newest entry brings back what was typed
+    pub fn browse_history(&mut self, history: &[String], older: bool) {
+        let (index, draft) = match std::mem::replace(&mut self.last_edit, LastEdit::Other) {
+            LastEdit::History(index, draft) => (index, draft),
+            _ => (history.len(), self.text.clone()),
+        };
+        let matches = |i: &usize| history[*i].starts_with(&draft) && history[*i] != self.text;
+        let found = if older {
+            (0..index).rev().find(matches)
+        } else {
+            (index + 1..history.len()).find(matches)
+        };
+        let index = match found {
+            Some(i) => {
+                self.set(history[i].clone(), true);
+                i
+            }
+            None if older => index,
+            None => {
+                self.set(draft.clone(), true);
+                history.len()
+            }
+        };
+        self.last_edit = LastEdit::History(index, draft);
+    }
+
+    /// complete the word before the cursor with the next or previous of the words `completions` gives for it,
+    /// cycling through them when called again
+    pub fn complete(&mut self, completions: impl FnOnce(&str) -> Vec<String>, forward: bool) {
+        let (start, words, index) = match std::mem::replace(&mut self.last_edit, LastEdit::Other) {
+            LastEdit::Complete(start, words, index) => {
+                let index = if forward {
+                    (index + 1) % words.len()
+                } else {
+                    index.checked_sub(1).unwrap_or(words.len() - 1)
+                };
+                (start, words, index)
+            }
+            _ => {
+                let start = self.text[..self.cursor].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+                let prefix = &self.text[start..self.cursor];
+                if prefix.is_empty() {
+                    return;
+                }
+                let mut words = completions(prefix);
+                if words.is_empty() {
+                    return;
+                }
+                words.push(prefix.to_string());
+                let index = if forward { 0 } else { words.len() - 2 };
+                (start, words, index)
+            }
+        };
+        self.text.replace_range(start..self.cursor, &words[index]);
+        self.cursor = start + words[index].len();
+        self.last_edit = LastEdit::Complete(start, words, index);
+    }
+
+    /// the part of the text to draw in `width` columns and the column of the cursor in it,
+    /// scrolling so the cursor stays on screen
+    pub fn view(&mut self, width: usize) -> (&str, usize) {
+        let width = width.max(1);
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        }
+        // leave room for the cursor after the last grapheme
+        while self.text[self.scroll..self.cursor].width() >= width {
+            self.scroll = next_grapheme(&self.text, self.scroll);
+        }
+        // scroll back when there is room, for example after deleting from the end
+        while self.scroll > 0 {
+            let prev = prev_grapheme(&self.text, self.scroll);
+            if self.text[prev..].width() >= width {
+                break;
+            }
+            self.scroll = prev;
+        }
+        let mut end = self.scroll;
+        for (i, grapheme) in self.text[self.scroll..].grapheme_indices(true) {
+            if self.text[self.scroll..self.scroll + i].width() + grapheme.width() > width {
+                break;
+            }
+            end = self.scroll + i + grapheme.len();
+        }
+        let column = self.text[self.scroll..self.cursor].width();
+        (&self.text[self.scroll..end], column)
+    }
+
+    fn move_to(&mut self, index: usize) {
+        self.cursor = index;
+        self.last_edit = LastEdit::Other;
+    }
+
+    /// start of the word before the cursor, skipping anything that isn't part of a word first
+    fn word_start(&self) -> usize {
+        let mut index = self.cursor;
+        let mut in_word = false;
+        for (i, grapheme) in self.text[..self.cursor].grapheme_indices(true).rev() {
+            let is_word = is_word(grapheme);
+            if in_word && !is_word {
+                break;
+            }
+            in_word |= is_word;
+            index = i;
+        }
+        index
+    }
+
+    /// end of the word after the cursor, skipping anything that isn't part of a word first
+    fn word_end(&self) -> usize {
+        let mut index = self.cursor;
+        let mut in_word = false;
+        for grapheme in self.text[self.cursor..].graphemes(true) {
+            let is_word = is_word(grapheme);
+            if in_word && !is_word {
+                break;
+            }
+            in_word |= is_word;
+            index += grapheme.len();
+        }
+        index
+    }
+
+    /// remove `range` and put it on the kill ring, joining it with the last kill if that was the previous edit
+    fn kill(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let killed: String = self.text.drain(range.clone()).collect();
+        let backwards = range.start < self.cursor;
+        self.cursor = range.start;
+        match self.kill_ring.last_mut() {
+            Some(last) if self.last_edit == LastEdit::Kill => {
+                if backwards {
+                    last.insert_str(0, &killed);
+                } else {
+                    last.push_str(&killed);
+                }
+            }
+            _ => {
+                if self.kill_ring.len() == KILL_RING_SIZE {
+                    self.kill_ring.remove(0);
+                }
+                self.kill_ring.push(killed);
+            }
+        }
+        self.last_edit = LastEdit::Kill;
+    }
+
+    fn yank_entry(&mut self, index: usize) {
+        let start = self.cursor;
+        self.text.insert_str(start, &self.kill_ring[index]);
+        self.cursor += self.kill_ring[index].len();
+        self.last_edit = LastEdit::Yank(start..self.cursor, index);
+    }
+}
+
+fn is_word(grapheme: &str) -> bool {
+    grapheme.chars().any(char::is_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_text(text: &str) -> LineEditor {
+        let mut editor = LineEditor::new();
+        editor.set(text.to_string(), true);
+        editor
+    }
+
+    #[test]
+    fn word_motions_skip_punctuation() {
+        let mut editor = with_text("foo-bar  baz");
+        let mut stops = vec![];
+        for _ in 0..4 {
+            editor.word_left();
+            stops.push(editor.cursor);
+        }
+        assert_eq!(stops, [9, 4, 0, 0]);
+        stops.clear();
+        for _ in 0..4 {
+            editor.word_right();
+            stops.push(editor.cursor);
+        }
+        assert_eq!(stops, [3, 7, 12, 12]);
+    }
+
+    #[test]
+    fn word_motions_use_graphemes() {
+        let mut editor = with_text("café naïve");
+        editor.word_left();
+        assert_eq!(&editor.text()[editor.cursor..], "naïve");
+        editor.word_left();
+        editor.word_right();
+        assert_eq!(&editor.text()[..editor.cursor], "café");
+    }
+
+    #[test]
+    fn kills_in_a_row_are_joined() {
+        let mut editor = with_text("one two three");
+        editor.kill_word_back();
+        editor.kill_word_back();
+        assert_eq!(editor.text(), "one ");
+        assert_eq!(editor.kill_ring, ["two three"]);
+        editor.home();
+        editor.kill_word_forward();
+        editor.kill_to_end();
+        assert_eq!(editor.text(), "");
+        assert_eq!(editor.kill_ring, ["two three", "one "]);
+
+        let mut editor = with_text("a.b c.d");
+        editor.kill_whitespace_word_back();
+        assert_eq!(editor.text(), "a.b ");
+        editor.left();
+        editor.kill_to_start();
+        assert_eq!(editor.text(), " ");
+        assert_eq!(editor.kill_ring, ["c.d", "a.b"]);
+    }
+
+    #[test]
+    fn yank_pop_cycles_through_the_kill_ring() {
+        let mut editor = with_text("one two three");
+        for _ in 0..3 {
+            editor.kill_word_back();
+            editor.left();
+        }
+        assert_eq!(editor.kill_ring, ["three", "two", "one"]);
+        assert_eq!(editor.text(), "  ");
+        editor.end();
+        editor.yank();
+        assert_eq!(editor.text(), "  one");
+        editor.yank_pop();
+        assert_eq!(editor.text(), "  two");
+        editor.yank_pop();
+        assert_eq!(editor.text(), "  three");
+        editor.yank_pop();
+        assert_eq!(editor.text(), "  one");
+        assert_eq!(editor.cursor, 5);
+        // only right after a yank
+        editor.insert('!');
+        editor.yank_pop();
+        assert_eq!(editor.text(), "  one!");
+    }
+
+    #[test]
+    fn kill_ring_keeps_the_last_kills() {
+        let mut editor = with_text("");
+        for i in 0..KILL_RING_SIZE + 2 {
+            editor.set(i.to_string(), true);
+            editor.kill_to_start();
+        }
+        assert_eq!(editor.kill_ring.len(), KILL_RING_SIZE);
+        assert_eq!(editor.kill_ring[0], "2");
+        editor.yank();
+        assert_eq!(editor.text(), (KILL_RING_SIZE + 1).to_string());
+    }
+
+    #[test]
+    fn view_scrolls_to_the_cursor() {
+        let mut editor = with_text("abcdefghij");
+        assert_eq!(editor.view(5), ("ghij", 4));
+        editor.home();
+        assert_eq!(editor.view(5), ("abcde", 0));
+        editor.end();
+        editor.view(5);
+        for _ in 0..3 {
+            editor.backspace();
+        }
+        // scrolls back once there's room
+        assert_eq!(editor.view(5), ("defg", 4));
+    }
+
+    #[test]
+    fn view_never_splits_wide_graphemes() {
+        let mut editor = with_text("日本語テキスト");
+        assert_eq!(editor.view(6), ("スト", 4));
+        assert_eq!(editor.view(5), ("スト", 4));
+        editor.home();
+        assert_eq!(editor.view(6), ("日本語", 0));
+        assert_eq!(editor.view(5), ("日本", 0));
+        editor.right();
+        editor.right();
+        assert_eq!(editor.view(5), ("日本", 4));
+        editor.right();
+        assert_eq!(editor.view(5), ("本語", 4));
+    }
+
+    fn history() -> Vec<String> {
+        ["buy milk", "call mum", "buy bread", "buy milk"].iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn history_keeps_what_was_typed() {
+        let history = history();
+        let mut editor = with_text("buy");
+        let mut shown = vec![];
+        for older in [true, true, true, true, false, false, false] {
+            editor.browse_history(&history, older);
+            shown.push(editor.text().to_string());
+        }
+        // entries that don't start with the typed text are skipped, and so is the one already shown
+        assert_eq!(shown, ["buy milk", "buy bread", "buy milk", "buy milk", "buy bread", "buy milk", "buy"]);
+        assert_eq!(editor.cursor, 3);
+    }
+
+    #[test]
+    fn editing_starts_a_new_history_search() {
+        let history = history();
+        let mut editor = with_text("");
+        editor.browse_history(&history, true);
+        editor.browse_history(&history, true);
+        assert_eq!(editor.text(), "buy bread");
+        editor.kill_word_back();
+        editor.insert('m');
+        editor.browse_history(&history, true);
+        assert_eq!(editor.text(), "buy milk");
+        editor.browse_history(&history, false);
+        assert_eq!(editor.text(), "buy m");
+    }
+
+    fn tags(prefix: &str) -> Vec<String> {
+        ["#home", "#hobby", "#work"].iter().filter(|tag| tag.starts_with(prefix)).map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn completion_cycles_back_to_the_typed_word() {
+        let mut editor = with_text("fix sink #ho");
+        let mut shown = vec![];
+        for _ in 0..4 {
+            editor.complete(tags, true);
+            shown.push(editor.text().to_string());
+        }
+        assert_eq!(shown, ["fix sink #home", "fix sink #hobby", "fix sink #ho", "fix sink #home"]);
+        editor.complete(tags, false);
+        assert_eq!(editor.text(), "fix sink #ho");
+        editor.complete(tags, false);
+        assert_eq!(editor.text(), "fix sink #hobby");
+        assert_eq!(editor.cursor, editor.text().len());
+    }
+
+    #[test]
+    fn completion_only_replaces_the_word_before_the_cursor() {
+        let mut editor = with_text("#w and more");
+        editor.home();
+        editor.right();
+        editor.right();
+        editor.complete(tags, false);
+        assert_eq!(editor.text(), "#work and more");
+        assert_eq!(editor.cursor, 5);
+        // nothing to complete
+        editor.insert(' ');
+        editor.complete(tags, true);
+        assert_eq!(editor.text(), "#work  and more");
+        editor.insert('#');
+        editor.insert('x');
+        editor.complete(tags, true);
+        assert_eq!(editor.text(), "#work #x and more");
+    }
+}
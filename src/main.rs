@@ -1,22 +1,62 @@
-use std::io::{self, prelude::*, BufRead};
+use std::collections::BTreeSet;
+use std::io::{self, prelude::*};
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
-use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use std::time::{Duration, Instant};
+
+use clipboard::{Clipboard, ClipboardProvider};
+use commands::Command;
+use date::Date;
+use history::{History, HistoryKind};
+use item::{DueStatus, StorageFormat, TodoItem, WordKind};
+use line_editor::LineEditor;
+use output::{Entry, OutputFormat};
+use sort::{Sort, SortKey};
+use storage::{load_list, ListFile};
 use crossterm::{
     queue,
     cursor,
     terminal,
     tty::IsTty,
-    event::{self, Event, KeyCode},
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     style::{
         Color,
+        ContentStyle,
         Print,
         Stylize,
+        StyledContent,
         PrintStyledContent,
     },
 };
 
-const TODO_LIST: &str = std::env!("TODO_LIST");
-const DONE_LIST: &str = std::env!("TODO_DONE_LIST");
+mod clipboard;
+mod commands;
+mod date;
+mod editor;
+mod history;
+mod item;
+mod line_editor;
+mod output;
+mod sort;
+mod storage;
+
+const DATA_DIR_NAME: &str = "todo_rs";
+const DEFAULT_TODO_FILE: &str = "todo.txt";
+const DEFAULT_DONE_FILE: &str = "done.txt";
+const DEFAULT_HISTORY_FILE: &str = "history.txt";
+/// where the last sort of each list is remembered, in the data directory
+const LAST_SORTS_FILE: &str = "sorts.txt";
+const DEFAULT_LIST_NAME: &str = "default";
+const LISTS_DIR_NAME: &str = "lists";
 const COLORS_LEN: usize = 12;
 const COLORS: [Color; COLORS_LEN] = [
     Color::Rgb{r: 255, g: 0,   b: 0},
@@ -34,6 +74,14 @@ const COLORS: [Color; COLORS_LEN] = [
 ];
 const MAX_WIDTH_SINGLE_PANE: u16 = 55;
 const CHECKBOX_WIDTH: usize = 4;
+/// width of the priority marker drawn after the checkbox of items with a priority
+const PRIORITY_WIDTH: usize = 2;
+/// how far each level of subtasks is indented
+const INDENT_WIDTH: usize = 2;
+/// shown after items that have a note
+const NOTE_MARKER: &str = "≡";
+const UNDO_LIMIT: usize = 1000;
+const RELOAD_INTERVAL: Duration = Duration::from_millis(500);
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 enum ListType {
@@ -56,12 +104,41 @@ enum InputDestination {
     NewItemBefore,
     NewItemAfter,
     EditItem,
+    NewList,
+    Search,
+    Filter,
+}
+
+impl InputDestination {
+    /// inputs for items share a history, the rest each have their own
+    fn history_kind(self) -> HistoryKind {
+        match self {
+            InputDestination::NewItem
+            | InputDestination::NewItemBefore
+            | InputDestination::NewItemAfter
+            | InputDestination::EditItem => HistoryKind::Item,
+            InputDestination::NewList => HistoryKind::List,
+            InputDestination::Search => HistoryKind::Search,
+            InputDestination::Filter => HistoryKind::Filter,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
 enum InputMode {
     Normal,
     Insert(InputDestination),
+    ListPicker,
+    /// choosing tags to filter the lists by
+    TagPicker,
+    /// choosing how to sort the current list
+    SortMenu,
+    /// changing the priority of the current item
+    Priority,
+    /// selecting a range of items in the current list
+    Visual,
+    /// asking whether to keep an edit of the whole list that removed items
+    ConfirmEdit,
 }
 
 fn use_repitition(repitition: &mut Option<String>) -> u128 {
@@ -71,85 +148,331 @@ fn use_repitition(repitition: &mut Option<String>) -> u128 {
     }
 }
 
-fn print_list(list: &[String]) {
-    for line in list {
-        println!("{}", line);
+/// print the items with one of the tags given in `args`, or all of them if there are none
+/// items keep their numbers when others are left out, so they can still be given to the subcommands
+fn print_list(list: &[TodoItem], done: bool, args: &Args) {
+    let name = args.list.as_deref().unwrap_or(DEFAULT_LIST_NAME);
+    let entries = list_entries(list, done, name, &args.tag);
+    print!("{}", output::format_entries(&entries, args.format, args.numbered));
+}
+
+/// the entries `print_list` prints for the items with one of `tags`
+fn list_entries<'a>(list: &'a [TodoItem], done: bool, name: &'a str, tags: &[String]) -> Vec<Entry<'a>> {
+    list.iter()
+        .enumerate()
+        .filter(|(_, item)| tags.is_empty() || tags.iter().any(|tag| item.has_tag(tag)))
+        .map(|(index, item)| Entry { index: index + 1, list: name, done, item })
+        .collect()
+}
+
+/// edit a list file in the user's editor, asking before removing any items
+/// changes other processes make to the file while the editor is open are kept
+fn edit_list_file(path: &Path, format: StorageFormat) -> io::Result<()> {
+    let items = load_list(path, format)?;
+    let edited = match editor::edit_items(&items)? {
+        Some(edited) => edited,
+        None => {
+            eprintln!("The editor failed, the list was not changed");
+            std::process::exit(1);
+        }
+    };
+    let (removed, added) = editor::diff_items(&items, &edited);
+    if !removed.is_empty() {
+        for item in &removed {
+            eprintln!("- {}", item.text);
+        }
+        for item in &added {
+            eprintln!("+ {}", item.text);
+        }
+        eprint!("The edit removes the items marked with -, keep it? [y/N] ");
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            eprintln!("The list was not changed");
+            return Ok(());
+        }
+    }
+    storage::update_list(path, format, |current| *current = storage::merge_items(&items, &edited, current))
+}
+
+/// Directory used for lists when no path is given on the command line or in the environment.
+/// Follows the XDG base directory spec: `$XDG_DATA_HOME/todo_rs`, falling back to `~/.local/share/todo_rs`
+fn default_data_dir() -> PathBuf {
+    let base = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match std::env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".local").join("share"),
+            None => PathBuf::from("."),
+        },
+    };
+    base.join(DATA_DIR_NAME)
+}
+
+/// Paths of the files backing the todo and done lists
+#[derive(Clone, Debug)]
+struct ListPaths {
+    todo: PathBuf,
+    done: PathBuf,
+}
+
+impl ListPaths {
+    /// resolve paths from the command line flags, then the environment (handled by structopt),
+    /// then the default data directory
+    fn resolve(args: &Args) -> Self {
+        let data_dir = default_data_dir();
+        Self {
+            todo: args.todo_file.clone().unwrap_or_else(|| data_dir.join(DEFAULT_TODO_FILE)),
+            done: args.done_file.clone().unwrap_or_else(|| data_dir.join(DEFAULT_DONE_FILE)),
+        }
+    }
+
+    /// paths of a named list stored in its own directory under `lists_dir`
+    fn named(lists_dir: &Path, name: &str) -> Self {
+        let dir = lists_dir.join(name);
+        Self {
+            todo: dir.join(DEFAULT_TODO_FILE),
+            done: dir.join(DEFAULT_DONE_FILE),
+        }
     }
 }
 
-fn print_todo() {
-    print_list(&load_list(TODO_LIST));
+fn resolve_lists_dir(args: &Args) -> PathBuf {
+    args.lists_dir.clone().unwrap_or_else(|| default_data_dir().join(LISTS_DIR_NAME))
+}
+
+/// a list name is used as a directory name so it can't be empty or contain path separators
+fn valid_list_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(std::path::is_separator)
 }
 
-fn print_done() {
-    print_list(&load_list(DONE_LIST));
+/// get the paths of the list called `name`, the default list uses the paths resolved from the arguments
+fn list_paths(args: &Args, name: Option<&str>) -> ListPaths {
+    match name {
+        None | Some(DEFAULT_LIST_NAME) => ListPaths::resolve(args),
+        Some(name) => ListPaths::named(&resolve_lists_dir(args), name),
+    }
+}
+
+/// names of the named lists found in `lists_dir`, sorted alphabetically
+fn find_list_names(lists_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = match std::fs::read_dir(lists_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| valid_list_name(name) && name != DEFAULT_LIST_NAME)
+            .collect(),
+        Err(_) => vec![],
+    };
+    names.sort();
+    names
 }
 
+/// split `s` into lines narrower than `max_length` columns
+/// lines are broken between grapheme clusters and measured by their display width
 fn word_wrap(s: &str, max_length: usize) -> Vec<String> {
     let mut res = vec![];
-    let mut s = s.trim().to_string();
+    let mut s = s.trim();
     'outer: loop {
-        for i in 0..s.len() {
-            if i + 1 >= max_length {
+        let mut width = 0;
+        for (i, grapheme) in s.grapheme_indices(true) {
+            width += grapheme.width();
+            if width + 1 > max_length {
                 let prev_word = if let Some(val) = s[..i].rfind(char::is_whitespace) {
                     val // find end of most recent word
-                } else {
+                } else if i > 0 {
                     i // no whitespace; break word
+                } else {
+                    grapheme.len() // a single grapheme wider than the line; don't loop forever
                 };
                 res.push(s[..prev_word].to_string()); // append to result
-                s = s[prev_word..] // remove part pushed to result
-                    .trim()
-                    .to_string();
+                s = s[prev_word..].trim(); // remove part pushed to result
+                if s.is_empty() {
+                    return res;
+                }
                 continue 'outer;
             }
         }
-        res.push(s);
+        res.push(s.to_string());
         break;
     }
     res
 }
 
-fn save_list(filename: &str, list: &[String]) {
-    let mut file = std::fs::File::create(filename).expect("Could not create file");
-    for line in list {
-        writeln!(file, "{}", line).expect("Could not write to file");
-    }
+/// byte index of the grapheme cluster before `index` in `s`
+fn prev_grapheme(s: &str, index: usize) -> usize {
+    s[..index]
+        .grapheme_indices(true)
+        .next_back()
+        .map_or(0, |(i, _)| i)
 }
 
-fn load_list(filename: &str) -> Vec<String> {
-    if let Ok(file) = std::fs::File::open(filename) {
-        io::BufReader::new(file)
-            .lines()
-            .map(|x| x.expect("Could not get line"))
-            .collect()
-    } else {
-        vec![]
-    }
+/// byte index of the grapheme cluster after `index` in `s`
+fn next_grapheme(s: &str, index: usize) -> usize {
+    s[index..]
+        .graphemes(true)
+        .next()
+        .map_or(index, |grapheme| index + grapheme.len())
 }
 
 fn color(index: usize) -> Color {
     COLORS[index % COLORS_LEN]
 }
 
+/// shown after a collapsed item in place of its subtasks
+fn collapsed_marker(hidden: usize) -> Option<String> {
+    if hidden == 0 {
+        None
+    } else {
+        Some(format!("[+{}]", hidden))
+    }
+}
+
+/// copy a run of items, shifting their depths so the first one is at `depth`
+fn with_depth(items: &[TodoItem], depth: usize) -> Vec<TodoItem> {
+    let base = items.first().map_or(0, |item| item.depth);
+    items
+        .iter()
+        .map(|item| TodoItem {
+            depth: (item.depth + depth).saturating_sub(base),
+            ..item.clone()
+        })
+        .collect()
+}
+
+/// color of the priority marker, from red for `A` through to blue for the lowest level
+fn priority_color(priority: char) -> Color {
+    match priority {
+        'A' => Color::Rgb{r: 255, g: 0,   b: 0},
+        'B' => Color::Rgb{r: 255, g: 128, b: 0},
+        'C' => Color::Rgb{r: 255, g: 255, b: 0},
+        'D' => Color::Rgb{r: 0,   g: 255, b: 0},
+        'E' => Color::Rgb{r: 0,   g: 128, b: 255},
+        _ => Color::DarkGrey,
+    }
+}
+
+/// style of an item's text, overdue and due today items stand out from the rainbow
+fn item_style(item: &TodoItem, index: usize, today: Option<Date>) -> ContentStyle {
+    match today.and_then(|today| item.due_status(today)) {
+        Some(DueStatus::Overdue) => ContentStyle::new().white().on_dark_red().bold(),
+        Some(DueStatus::Today) => ContentStyle::new().black().on_dark_yellow(),
+        _ => ContentStyle::new().with(color(index)),
+    }
+}
+
+/// print a line of an item's text with todo.txt projects, contexts and extensions styled distinctly
+/// and any matches of `highlight` highlighted
+fn draw_words(stdout: &mut io::Stdout, line: &str, style: ContentStyle, highlight: Option<&str>) -> crossterm::Result<()> {
+    let highlights = highlight.map_or(vec![], |pattern| find_matches(line, pattern));
+    let highlighted = |pos: usize| highlights.iter().any(|range| range.contains(&pos));
+    let mut start = 0; // byte offset of the current word in line
+    for (i, word) in line.split(' ').enumerate() {
+        if i > 0 {
+            if highlighted(start - 1) {
+                queue!(stdout, PrintStyledContent(" ".on_yellow()))?;
+            } else {
+                queue!(stdout, PrintStyledContent(style.apply(' ')))?;
+            }
+        }
+        // split the word where highlights start and end so each piece is styled on its own
+        let mut pieces = vec![0, word.len()];
+        for range in &highlights {
+            for bound in [range.start, range.end] {
+                if bound > start && bound < start + word.len() {
+                    pieces.push(bound - start);
+                }
+            }
+        }
+        pieces.sort_unstable();
+        pieces.dedup();
+        for bounds in pieces.windows(2) {
+            let piece = &word[bounds[0]..bounds[1]];
+            let styled = match WordKind::of(word) {
+                WordKind::Plain => style.apply(piece),
+                WordKind::Project => style.apply(piece).bold().underlined(),
+                WordKind::Context => style.apply(piece).bold().italic(),
+                WordKind::Hashtag => style.apply(piece).bold().on_dark_grey(),
+                WordKind::KeyValue => style.apply(piece).dark_grey(),
+            };
+            let styled = if highlighted(start + bounds[0]) {
+                styled.black().on_yellow()
+            } else {
+                styled
+            };
+            queue!(stdout, PrintStyledContent(styled))?;
+        }
+        start += word.len() + 1;
+    }
+    Ok(())
+}
+
+/// case insensitive byte ranges of `pattern` in `text`
+fn find_matches(text: &str, pattern: &str) -> Vec<Range<usize>> {
+    if pattern.is_empty() {
+        return vec![];
+    }
+    // lowercasing can change how many bytes a character takes, so remember which character each byte came from
+    let mut lowered = String::with_capacity(text.len());
+    let mut sources = Vec::with_capacity(text.len());
+    for (i, ch) in text.char_indices() {
+        lowered.extend(ch.to_lowercase());
+        sources.resize(lowered.len(), i..i + ch.len_utf8());
+    }
+    lowered
+        .match_indices(&lowercase(pattern))
+        .map(|(start, matched)| sources[start].start..sources[start + matched.len() - 1].end)
+        .collect()
+}
+
+fn matches(text: &str, pattern: &str) -> bool {
+    lowercase(text).contains(&lowercase(pattern))
+}
+
+/// lowercase one character at a time, the same way `find_matches` does
+fn lowercase(text: &str) -> String {
+    text.chars().flat_map(char::to_lowercase).collect()
+}
+
 struct List {
-    items: Vec<String>,
+    items: Vec<TodoItem>,
     list_type: ListType,
     current_index: usize,
     y_offset: usize,
+    /// only items containing this are shown
+    filter: Option<String>,
+    /// the sort last used on this list
+    last_sort: Option<Sort>,
+    /// only items with one of these tags are shown, unless it's empty
+    tags: Vec<String>,
+    /// where the selection started in visual mode, the selection goes from here to the cursor
+    anchor: Option<usize>,
+    /// indices of the items marked to be changed together
+    marked: BTreeSet<usize>,
 }
 
 impl List {
-    fn new(items: Vec<String>, list_type: ListType) -> Self {
+    fn new(items: Vec<TodoItem>, list_type: ListType) -> Self {
         Self {
             items,
             list_type,
             current_index: 0,
             y_offset: 0,
+            filter: None,
+            last_sort: None,
+            tags: vec![],
+            anchor: None,
+            marked: BTreeSet::new(),
         }
     }
 
     fn draw(
         &mut self,
+        name: &str,
+        highlight: Option<&str>,
         pos: (u16, u16),
         size: (u16, u16),
         stdout: &mut io::Stdout,
@@ -158,22 +481,52 @@ impl List {
             self.update_y_offset(size);
         }
         let checkbox = self.get_checkbox();
+        let marked_checkbox = format!("[{}] ", "*".magenta().bold());
         let mut offset = self.y_offset as u16;
         queue!(
             stdout,
             cursor::MoveTo(pos.0, pos.1),
             self.get_title(),
         )?;
+        if name != DEFAULT_LIST_NAME {
+            queue!(stdout, Print(format!(" ({})", name)))?;
+        }
+        if let Some(filter) = &self.filter {
+            queue!(stdout, PrintStyledContent(format!(" filter: {}", filter).dark_grey()))?;
+        }
+        if !self.tags.is_empty() {
+            queue!(stdout, PrintStyledContent(format!(" tags: {}", self.tags.join(" ")).dark_grey()))?;
+        }
+        if !self.marked.is_empty() {
+            queue!(stdout, PrintStyledContent(format!(" {} marked", self.marked.len()).magenta()))?;
+        }
         let max = self.get_max_line_width(size);
+        // done items aren't due anymore
+        let today = match self.list_type {
+            ListType::Todo => Some(Date::today()),
+            ListType::Done => None,
+        };
         let mut idx = 0u16;
-        'outer: for line in &self.items {
-            let mut first = true;
-            for subline in word_wrap(line, max as usize) {
-                let checkbox = if first {
-                    first = false;
-                    &checkbox
-                } else {
+        let selection = self.selection();
+        'outer: for (index, item) in self.visible_items() {
+            let indent = self.indent_width(item, max) as u16;
+            let selected = selection.as_ref().is_some_and(|selection| selection.contains(&index));
+            let suffix = self.item_suffix(index);
+            let lines = self.wrap_item(index, max);
+            let last = lines.len().saturating_sub(1);
+            for (line_index, subline) in lines.iter().enumerate() {
+                let first = line_index == 0;
+                let checkbox = if !first {
                     "    "
+                } else if self.marked.contains(&index) {
+                    marked_checkbox.as_str()
+                } else {
+                    checkbox.as_str()
+                };
+                let marker = match item.priority {
+                    Some(priority) if first => format!("{} ", priority).with(priority_color(priority)).bold(),
+                    Some(_) => "  ".to_string().stylize(),
+                    None => String::new().stylize(),
                 };
                 if idx + 2 > size.1 { // offscreen
                     break 'outer;
@@ -184,16 +537,56 @@ impl List {
                 }
                 queue!(
                     stdout,
-                    cursor::MoveTo(pos.0, pos.1 + idx + 1),
+                    cursor::MoveTo(pos.0 + indent, pos.1 + idx + 1),
                     Print(checkbox),
-                    PrintStyledContent(subline.with(color(idx as usize))),
+                    PrintStyledContent(marker),
                 )?;
+                let style = if selected {
+                    item_style(item, idx as usize, today).reverse()
+                } else {
+                    item_style(item, idx as usize, today)
+                };
+                let suffix = if line_index == last { suffix.as_deref() } else { None };
+                match suffix.and_then(|suffix| Some((subline.strip_suffix(suffix)?, suffix))) {
+                    Some((text, suffix)) => {
+                        draw_words(stdout, text, style, highlight)?;
+                        queue!(stdout, PrintStyledContent(suffix.dark_grey()))?;
+                    }
+                    None => draw_words(stdout, subline, style, highlight)?,
+                }
                 idx += 1;
             }
         }
         Ok(())
     }
 
+    /// the lines the item at `index` takes up when drawn, not counting the indent, checkbox or priority marker
+    fn wrap_item(&self, index: usize, max: usize) -> Vec<String> {
+        let item = &self.items[index];
+        let mut max = max - self.indent_width(item, max);
+        if item.priority.is_some() {
+            max = max.saturating_sub(PRIORITY_WIDTH).max(1);
+        }
+        match self.item_suffix(index) {
+            Some(suffix) => word_wrap(&format!("{} {}", item.text, suffix), max),
+            None => word_wrap(&item.text, max),
+        }
+    }
+
+    /// drawn after an item, marking that it has a note and how many subtasks are hidden if it's collapsed
+    fn item_suffix(&self, index: usize) -> Option<String> {
+        let note = self.items[index].note.as_ref().map(|_| NOTE_MARKER.to_string());
+        match (note, collapsed_marker(self.hidden_children(index))) {
+            (Some(note), Some(hidden)) => Some(format!("{} {}", note, hidden)),
+            (note, hidden) => note.or(hidden),
+        }
+    }
+
+    /// how far an item is indented, subtasks are never indented past half of the line
+    fn indent_width(&self, item: &TodoItem, max: usize) -> usize {
+        (item.depth * INDENT_WIDTH).min(max / 2)
+    }
+
     fn get_max_line_width(&self, size: (u16, u16)) -> usize {
         size.0 as usize - CHECKBOX_WIDTH
     }
@@ -212,122 +605,445 @@ impl List {
         }
     }
 
+    fn is_visible(&self, index: usize) -> bool {
+        let item = match self.items.get(index) {
+            Some(item) => item,
+            None => return false,
+        };
+        self.filter.as_ref().is_none_or(|filter| matches(&item.text, filter))
+            && (self.tags.is_empty() || self.tags.iter().any(|tag| item.has_tag(tag)))
+            && !self.in_collapsed(index)
+    }
+
+    /// true if an item that the item at `index` is a subtask of is collapsed
+    fn in_collapsed(&self, index: usize) -> bool {
+        let mut depth = self.items[index].depth;
+        for item in self.items[..index].iter().rev() {
+            if depth == 0 {
+                break;
+            }
+            if item.depth < depth {
+                if item.collapsed {
+                    return true;
+                }
+                depth = item.depth;
+            }
+        }
+        false
+    }
+
+    /// index just past the subtasks of the item at `index`
+    fn subtree_end(&self, index: usize) -> usize {
+        let depth = self.items[index].depth;
+        (index + 1..self.items.len())
+            .find(|&i| self.items[i].depth <= depth)
+            .unwrap_or(self.items.len())
+    }
+
+    fn has_children(&self, index: usize) -> bool {
+        matches!(self.items.get(index + 1), Some(next) if next.depth > self.items[index].depth)
+    }
+
+    /// number of subtasks hidden by collapsing the item at `index`
+    fn hidden_children(&self, index: usize) -> usize {
+        if self.items[index].collapsed {
+            self.subtree_end(index) - index - 1
+        } else {
+            0
+        }
+    }
+
+    /// make the current item and its subtasks subtasks of the item above
+    fn indent(&mut self) {
+        if !self.has_current() {
+            return;
+        }
+        let index = self.current_index;
+        let depth = self.items[index].depth;
+        if index == 0 || self.items[index - 1].depth < depth {
+            return;
+        }
+        let end = self.subtree_end(index);
+        for item in &mut self.items[index..end] {
+            item.depth += 1;
+        }
+        // expand whatever the item ended up in so it doesn't disappear
+        self.expand_to(index);
+    }
+
+    /// expand the items that the item at `index` is a subtask of
+    fn expand_to(&mut self, index: usize) {
+        let mut depth = self.items[index].depth;
+        for item in self.items[..index].iter_mut().rev() {
+            if depth == 0 {
+                break;
+            }
+            if item.depth < depth {
+                item.collapsed = false;
+                depth = item.depth;
+            }
+        }
+    }
+
+    /// move the current item and its subtasks out a level
+    fn outdent(&mut self) {
+        if !self.has_current() || self.items[self.current_index].depth == 0 {
+            return;
+        }
+        let end = self.subtree_end(self.current_index);
+        for item in &mut self.items[self.current_index..end] {
+            item.depth -= 1;
+        }
+    }
+
+    /// collapse or expand the subtasks of the current item
+    fn toggle_collapsed(&mut self) {
+        if self.has_current() && self.has_children(self.current_index) {
+            let item = &mut self.items[self.current_index];
+            item.collapsed = !item.collapsed;
+        }
+    }
+
+    /// the items that pass the filter along with their index
+    fn visible_items(&self) -> impl Iterator<Item = (usize, &TodoItem)> {
+        self.items
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| self.is_visible(*i))
+    }
+
+    /// true if the cursor is on an item that is shown
+    fn has_current(&self) -> bool {
+        self.is_visible(self.current_index)
+    }
+
+    /// index of the next visible item after `index`, wrapping around to the top
+    fn next_visible(&self, index: usize) -> Option<usize> {
+        let len = self.items.len();
+        (1..=len)
+            .map(|offset| (index + offset) % len)
+            .find(|&i| self.is_visible(i))
+    }
+
+    /// index of the previous visible item before `index`, wrapping around to the bottom
+    fn prev_visible(&self, index: usize) -> Option<usize> {
+        let len = self.items.len();
+        (1..=len)
+            .map(|offset| (index + len - offset) % len)
+            .find(|&i| self.is_visible(i))
+    }
+
+    /// move the cursor to the closest visible item if the current one is hidden
+    fn fix_current_index(&mut self) {
+        if self.has_current() {
+            return;
+        }
+        let len = self.items.len();
+        let index = self.current_index.min(len.saturating_sub(1));
+        self.current_index = (index..len)
+            .find(|&i| self.is_visible(i))
+            .or_else(|| (0..index).rev().find(|&i| self.is_visible(i)))
+            .unwrap_or(index);
+    }
+
+    /// replace all of the items, keeping the cursor on the same item if it is still there
+    fn replace_items(&mut self, items: Vec<TodoItem>) {
+        if items != self.items {
+            // marks and the visual selection are indices, which may now point at other items
+            self.marked.clear();
+            self.anchor = None;
+        }
+        let current = self.items.get(self.current_index).cloned();
+        let old_index = self.current_index;
+        self.items = items;
+        if let Some(current) = current {
+            // take the copy of the item closest to where it was
+            if let Some(index) = self.items
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| **item == current)
+                .map(|(i, _)| i)
+                .min_by_key(|i| i.abs_diff(old_index))
+            {
+                self.current_index = index;
+            }
+        }
+        self.fix_current_index();
+    }
+
+    fn set_filter(&mut self, filter: Option<String>) {
+        self.filter = filter.filter(|filter| !filter.is_empty());
+        self.fix_current_index();
+    }
+
+    fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags = tags;
+        self.fix_current_index();
+    }
+
+    /// every tag used in the list
+    fn all_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self.items.iter().flat_map(TodoItem::tags).collect();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
+
     fn move_to_top(&mut self) {
         self.current_index = 0;
+        self.fix_current_index();
     }
 
     fn move_to_bottom(&mut self) {
-        let len = self.items.len();
-        self.current_index = if len == 0 {
-            0
-        } else {
-            len - 1
-        };
+        self.current_index = self.items.len().saturating_sub(1);
+        self.fix_current_index();
     }
 
     // TODO: OPTIMIZE have move_up/move_down just get the repitition count instead of using repeat macro
     fn move_up(&mut self) {
-        let len = self.items.len();
-        if len == 0 {
-            return;
-        }
-        if self.current_index == 0 {
-            self.current_index = len - 1;
-        } else {
-            self.current_index -= 1;
+        if let Some(index) = self.prev_visible(self.current_index) {
+            self.current_index = index;
         }
     }
 
     fn move_down(&mut self) {
+        if let Some(index) = self.next_visible(self.current_index) {
+            self.current_index = index;
+        }
+    }
+
+    /// move the cursor to the next item matching `pattern`, searching backwards if `forward` is false
+    /// if `inclusive` is true the current item counts as a match
+    fn jump_to_match(&mut self, pattern: &str, forward: bool, inclusive: bool) {
         let len = self.items.len();
-        if len == 0 {
+        if len == 0 || pattern.is_empty() {
             return;
         }
-        self.current_index += 1;
-        self.current_index %= len;
+        let first = if inclusive { 0 } else { 1 };
+        let found = (first..first + len)
+            .map(|offset| if forward {
+                (self.current_index + offset) % len
+            } else {
+                (self.current_index + len * 2 - offset) % len
+            })
+            .find(|&i| self.is_visible(i) && matches(&self.items[i].text, pattern));
+
+        if let Some(index) = found {
+            self.current_index = index;
+        }
     }
 
+    /// swap the current item and its subtasks with the visible item above at the same level
     fn shift_up(&mut self) {
-        if self.items.is_empty() {
-            return;
-        }
-        if self.current_index > 0 {
-            self.items.swap(self.current_index, self.current_index - 1);
-        } else {
-            let item = self.items.remove(0);
-            self.items.push(item);
+        if self.has_current() {
+            self.current_index = self.shift_range_up(self.current_index..self.subtree_end(self.current_index));
         }
-        self.move_up();
     }
 
+    /// swap the current item and its subtasks with the visible item below at the same level
     fn shift_down(&mut self) {
-        let len = self.items.len();
-        if len == 0 {
-            return;
+        if self.has_current() {
+            self.current_index = self.shift_range_down(self.current_index..self.subtree_end(self.current_index));
+        }
+    }
+
+    /// swap a run of items at the same level, along with their subtasks, with the visible item above them
+    /// returns where the run starts after moving it
+    fn shift_range_up(&mut self, range: Range<usize>) -> usize {
+        let depth = self.items[range.start].depth;
+        let prev = (0..range.start)
+            .rev()
+            .take_while(|&i| self.items[i].depth >= depth)
+            .find(|&i| self.items[i].depth == depth && self.is_visible(i));
+        let target = match prev {
+            Some(prev) => prev,
+            None if depth == 0 => self.items.len() - range.len(),
+            None => return range.start,
+        };
+        self.move_range(range, target)
+    }
+
+    /// swap a run of items at the same level, along with their subtasks, with the visible item below them
+    /// returns where the run starts after moving it
+    fn shift_range_down(&mut self, range: Range<usize>) -> usize {
+        let depth = self.items[range.start].depth;
+        let mut next = range.end;
+        while next < self.items.len() && self.items[next].depth == depth && !self.is_visible(next) {
+            next = self.subtree_end(next);
         }
-        if self.current_index + 1 < len {
-            self.items.swap(self.current_index, self.current_index + 1);
+        let target = if next < self.items.len() && self.items[next].depth == depth {
+            self.subtree_end(next) - range.len()
+        } else if depth == 0 {
+            0
         } else {
-            let item = self.items.remove(len - 1);
-            self.items.insert(0, item)
+            return range.start;
+        };
+        self.move_range(range, target)
+    }
+
+    /// move a run of items so it starts at `target` once moved
+    fn move_range(&mut self, range: Range<usize>, target: usize) -> usize {
+        let moved: Vec<TodoItem> = self.items.drain(range).collect();
+        self.items.splice(target..target, moved);
+        target
+    }
+
+    /// the visible items selected in visual mode that aren't subtasks of another selected item
+    fn selected_roots(&self) -> Vec<usize> {
+        let anchor = match self.anchor {
+            Some(anchor) if anchor < self.items.len() && self.current_index < self.items.len() => anchor,
+            _ => return vec![],
+        };
+        let mut roots = vec![];
+        let mut end = 0;
+        for index in anchor.min(self.current_index)..=anchor.max(self.current_index) {
+            if index >= end && self.is_visible(index) {
+                roots.push(index);
+                end = self.subtree_end(index);
+            }
+        }
+        roots
+    }
+
+    /// copies of the items selected in visual mode along with their subtasks
+    fn clone_selection(&self) -> Vec<TodoItem> {
+        self.clone_roots(&self.selected_roots())
+    }
+
+    /// copies of the marked items along with their subtasks
+    fn clone_marked(&self) -> Vec<TodoItem> {
+        self.clone_roots(&self.marked_roots())
+    }
+
+    fn clone_roots(&self, roots: &[usize]) -> Vec<TodoItem> {
+        roots
+            .iter()
+            .flat_map(|&root| self.items[root..self.subtree_end(root)].to_vec())
+            .collect()
+    }
+
+    /// mark or unmark the current item
+    fn toggle_mark(&mut self) {
+        if self.has_current() && !self.marked.remove(&self.current_index) {
+            self.marked.insert(self.current_index);
+        }
+    }
+
+    /// the marked items that aren't subtasks of another marked item
+    fn marked_roots(&self) -> Vec<usize> {
+        let mut roots = vec![];
+        let mut end = 0;
+        for &index in self.marked.range(..self.items.len()) {
+            if index >= end {
+                roots.push(index);
+                end = self.subtree_end(index);
+            }
         }
-        self.move_down();
+        roots
+    }
+
+    /// the items selected in visual mode along with their subtasks
+    fn selection(&self) -> Option<Range<usize>> {
+        let roots = self.selected_roots();
+        Some(*roots.first()?..self.subtree_end(*roots.last()?))
     }
 
-    fn remove(&mut self) -> Option<String> {
-        if self.items.is_empty() {
+    /// the selection if all of the selected items are at the same level, which is needed to move or sort them together
+    fn level_selection(&self) -> Option<Range<usize>> {
+        let range = self.selection()?;
+        let roots = self.selected_roots();
+        if roots.iter().all(|&root| self.items[root].depth == self.items[range.start].depth) {
+            Some(range)
+        } else {
             None
+        }
+    }
+
+    /// move the selection along with the cursor
+    fn shift_selection(&mut self, up: bool) {
+        let roots = self.selected_roots();
+        let (Some(range), Some(anchor)) = (self.level_selection(), self.anchor) else {
+            return;
+        };
+        let start = range.start;
+        let last = roots[roots.len() - 1];
+        let target = if up { self.shift_range_up(range) } else { self.shift_range_down(range) };
+        let last = last + target - start;
+        if anchor <= self.current_index {
+            self.anchor = Some(target);
+            self.current_index = last;
         } else {
-            let res = Some(self.items.remove(self.current_index));
-            let len = self.items.len();
-            if len == 0 {
-                self.current_index = 0
-            } else if self.current_index >= len {
-                self.current_index = len - 1;
-            }
-            res
+            self.anchor = Some(last);
+            self.current_index = target;
         }
     }
 
-    fn add(&mut self, item: String) {
-        self.items.push(item);
+    /// remove the current item along with its subtasks, which keep their depth relative to it
+    fn remove(&mut self) -> Vec<TodoItem> {
+        if !self.has_current() {
+            return vec![];
+        }
+        let end = self.subtree_end(self.current_index);
+        let res = self.items.drain(self.current_index..end).collect();
+        self.fix_current_index();
+        res
     }
 
-    fn insert(&mut self, item: String, index: usize) {
-        self.items.insert(index, item);
+    /// add items to the end of the list at the top level
+    fn add(&mut self, items: &[TodoItem]) {
+        self.items.extend(with_depth(items, 0));
     }
 
-    fn insert_before(&mut self, item: String) {
-        self.insert(item, self.current_index);
+    /// insert items above the current item at the same level as it
+    fn insert_before(&mut self, items: &[TodoItem]) {
+        let index = self.current_index.min(self.items.len());
+        let depth = self.items.get(index).map_or(0, |item| item.depth);
+        self.items.splice(index..index, with_depth(items, depth));
     }
 
-    fn insert_many_before(&mut self, items: &Vec<String>) {
-        for item in items {
-            self.insert_before(item.clone());
+    /// insert items below the current item, as its first subtasks if it has visible ones
+    /// and the cursor moves to the last of them
+    fn insert_after(&mut self, items: &[TodoItem]) {
+        if items.is_empty() {
+            return;
         }
+        let (index, depth) = if !self.has_current() {
+            (self.items.len(), 0)
+        } else if self.has_children(self.current_index) && !self.items[self.current_index].collapsed {
+            (self.current_index + 1, self.items[self.current_index].depth + 1)
+        } else {
+            (self.subtree_end(self.current_index), self.items[self.current_index].depth)
+        };
+        let items = with_depth(items, depth);
+        self.current_index = index + items.iter().rposition(|item| item.depth == depth).unwrap_or(0);
+        self.items.splice(index..index, items);
     }
 
-    fn insert_after(&mut self, item: String) {
-        let mut index = self.current_index + 1;
-        if index > self.items.len() {
-            index = self.current_index;
+    /// replace the text of the current item, keeping its metadata
+    fn set_current_text(&mut self, text: String) {
+        if self.has_current() {
+            self.items[self.current_index].set_text(&text);
         }
-        self.insert(item, index);
-        self.move_down()
     }
 
-    fn insert_many_after(&mut self, items: &Vec<String>) {
-        for item in items {
-            self.insert_after(item.clone());
+    fn current_mut(&mut self) -> Option<&mut TodoItem> {
+        if self.has_current() {
+            self.items.get_mut(self.current_index)
+        } else {
+            None
         }
     }
 
-    fn set_current(&mut self, item: String) {
-        self.items[self.current_index] = item;
+    /// the current item along with its subtasks
+    fn clone_subtree(&self) -> Vec<TodoItem> {
+        if !self.has_current() {
+            return vec![];
+        }
+        self.items[self.current_index..self.subtree_end(self.current_index)].to_vec()
     }
 
-    fn clone_current(&mut self) -> Option<String> {
-        if self.items.is_empty() {
+    fn clone_current(&mut self) -> Option<TodoItem> {
+        if !self.has_current() {
             None
         } else {
             Some(self.items[self.current_index].clone())
@@ -338,10 +1054,10 @@ impl List {
         let max = self.get_max_line_width(size);
         let mut y = 1; // start at one for title
         // the logic is the position of the current index
-        // is the sum of all the lines before the current line
+        // is the sum of all the visible lines before the current line
         // plus 1 for the title offset
-        for i in 0..self.current_index {
-            y += word_wrap(&self.items[i], max).len();
+        for (i, _) in self.visible_items().take_while(|(i, _)| *i < self.current_index) {
+            y += self.wrap_item(i, max).len();
         }
         y
     }
@@ -353,7 +1069,10 @@ impl List {
         stdout: &mut io::Stdout,
     ) -> crossterm::Result<()> {
         let y = self.get_y_pos(size).checked_sub(self.y_offset).unwrap_or(1) as u16;
-        queue!(stdout, cursor::MoveTo(pos.0, pos.1 + y))
+        let x = self.items
+            .get(self.current_index)
+            .map_or(0, |item| self.indent_width(item, self.get_max_line_width(size))) as u16;
+        queue!(stdout, cursor::MoveTo(pos.0 + x, pos.1 + y))
     }
 
     fn update_y_offset(&mut self, size: (u16, u16)) {
@@ -370,58 +1089,302 @@ impl List {
         y + 1 > size.1 as usize + self.y_offset || y <= self.y_offset
     }
 
-    fn sort(&mut self) {
-        self.items.sort();
+    /// sort the items, keeping the cursor on the same item, and remember the sort
+    fn sort(&mut self, sort: Sort) {
+        self.sort_range(sort, 0..self.items.len());
+    }
+
+    /// sort just the items in `range`
+    fn sort_range(&mut self, sort: Sort, range: Range<usize>) {
+        let range = range.start.min(self.items.len())..range.end.min(self.items.len());
+        let mut items = self.items.clone();
+        let sorted = sort.apply(items[range.clone()].to_vec());
+        items.splice(range, sorted);
+        self.replace_items(items);
+        self.last_sort = Some(sort);
     }
 }
 
-struct TodoApp {
-    running: bool,
-    stdout: io::Stdout,
+/// A todo list and its done list, stored together under a name
+struct NamedList {
+    name: String,
+    format: StorageFormat,
     todo: List,
     done: List,
-    list_type: ListType,
-    input_mode: InputMode,
-    input_string: String,
-    input_string_index: usize,
-    terminal_size: (u16, u16),
-    one_pane: bool,
-    clipboard: Vec<String>,
-    repitition_modifier: Option<String>,
+    todo_file: ListFile,
+    done_file: ListFile,
+    /// the last sorts of the todo and done lists as they were loaded or saved
+    saved_sorts: (Option<Sort>, Option<Sort>),
 }
 
-impl TodoApp {
-    fn new() -> Self {
-        let terminal_size = terminal::size().expect("Could not get terminal size");
-        Self {
-            running: true,
-            stdout: io::stdout(),
-            todo: List::new(load_list(TODO_LIST), ListType::Todo),
-            done: List::new(load_list(DONE_LIST), ListType::Done),
-            list_type: ListType::Todo,
-            input_mode: InputMode::Normal,
-            input_string: "".to_string(),
-            input_string_index: 0,
-            terminal_size,
-            one_pane: terminal_size.0 <= MAX_WIDTH_SINGLE_PANE,
-            clipboard: vec![],
-            repitition_modifier: None,
+impl NamedList {
+    fn load(name: String, paths: ListPaths, format: StorageFormat) -> io::Result<Self> {
+        let (todo_file, todo_items) = ListFile::load(paths.todo, format)?;
+        let (done_file, done_items) = ListFile::load(paths.done, format)?;
+        let sorts_path = default_data_dir().join(LAST_SORTS_FILE);
+        let saved_sorts = (
+            sort::load_last_sort(&sorts_path, &todo_file.path),
+            sort::load_last_sort(&sorts_path, &done_file.path),
+        );
+        let mut todo = List::new(todo_items, ListType::Todo);
+        let mut done = List::new(done_items, ListType::Done);
+        (todo.last_sort, done.last_sort) = saved_sorts;
+        Ok(Self {
+            name,
+            format,
+            todo,
+            done,
+            todo_file,
+            done_file,
+            saved_sorts,
+        })
+    }
+
+    /// merge in changes other processes made to the list files
+    /// returns true if either list changed
+    fn reload(&mut self) -> io::Result<bool> {
+        let mut todo_items = self.todo.items.clone();
+        let mut done_items = self.done.items.clone();
+        let todo_changed = self.todo_file.reload(&mut todo_items)?;
+        let done_changed = self.done_file.reload(&mut done_items)?;
+        if todo_changed {
+            self.todo.replace_items(todo_items);
         }
+        if done_changed {
+            self.done.replace_items(done_items);
+        }
+        Ok(todo_changed || done_changed)
     }
 
-    fn go_to_current_index(&mut self) -> crossterm::Result<()> {
-        let size = if self.one_pane {
-            self.terminal_size
-        } else {
-            (self.terminal_size.0 / 2, self.terminal_size.1)
-        };
-        match self.list_type {
-            ListType::Todo => self.todo.go_to_current_index(
-                (0, 0),
-                size,
-                &mut self.stdout,
+    /// save both lists, merging in anything other processes changed since they were loaded
+    fn save(&mut self) -> io::Result<()> {
+        let mut todo_items = self.todo.items.clone();
+        let mut done_items = self.done.items.clone();
+        if self.todo_file.save(&mut todo_items)? {
+            self.todo.replace_items(todo_items);
+        }
+        if self.done_file.save(&mut done_items)? {
+            self.done.replace_items(done_items);
+        }
+        let sorts = (self.todo.last_sort, self.done.last_sort);
+        if sorts != self.saved_sorts {
+            let lists = [(self.todo_file.path.as_path(), sorts.0), (self.done_file.path.as_path(), sorts.1)];
+            // remembering the sorts is only a convenience, so not being able to isn't worth failing the save for
+            if sort::save_last_sorts(&default_data_dir().join(LAST_SORTS_FILE), &lists).is_ok() {
+                self.saved_sorts = sorts;
+            }
+        }
+        Ok(())
+    }
+
+    /// move the current todo item and its subtasks to the done list
+    /// a recurring item is replaced by a fresh copy of it and its subtasks that is due the next time it comes back
+    fn check_item(&mut self) {
+        let index = self.todo.current_index;
+        let mut items = self.todo.remove();
+        if let Some(next) = items.first().and_then(|item| item.next_occurrence(Date::today())) {
+            let copy: Vec<TodoItem> = std::iter::once(next)
+                .chain(items[1..].iter().map(|item| TodoItem {
+                    created: Some(item::now()),
+                    ..item.clone()
+                }))
+                .collect();
+            // leave the cursor after the copy so checking again doesn't check it too
+            self.todo.current_index = index + copy.len();
+            self.todo.items.splice(index..index, copy);
+            self.todo.fix_current_index();
+        }
+        for item in &mut items {
+            item.complete();
+        }
+        self.done.add(&items);
+    }
+
+    /// move the current done item and its subtasks back to the todo list
+    fn uncheck_item(&mut self) {
+        let mut items = self.done.remove();
+        for item in &mut items {
+            item.uncomplete();
+        }
+        self.todo.add(&items);
+    }
+
+    fn delete_item(&mut self) {
+        self.done.remove();
+    }
+
+    fn clear_marks(&mut self) {
+        self.todo.marked.clear();
+        self.done.marked.clear();
+    }
+
+    /// words in either list that start with `prefix` ignoring case, the most used first
+    fn completions(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.to_lowercase();
+        let mut counts: Vec<(String, usize)> = vec![];
+        let words = self.todo.items.iter()
+            .chain(&self.done.items)
+            .flat_map(|item| item.text.split_whitespace())
+            .map(|word| word.trim_end_matches(&[',', '.', ';', ':', '!', '?'][..]));
+        for word in words {
+            if word.len() <= prefix.len() || !word.to_lowercase().starts_with(&prefix) {
+                continue;
+            }
+            match counts.iter_mut().find(|(w, _)| w == word) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((word.to_string(), 1)),
+            }
+        }
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts.into_iter().map(|(word, _)| word).collect()
+    }
+
+    fn list(&mut self, list_type: ListType) -> &mut List {
+        match list_type {
+            ListType::Todo => &mut self.todo,
+            ListType::Done => &mut self.done,
+        }
+    }
+
+    /// run `f` with the cursor on each of `roots` in one of the lists, from the top down
+    /// `roots` must be sorted and none of them can be subtasks of another
+    fn for_each_root(&mut self, list_type: ListType, roots: &[usize], f: fn(&mut NamedList)) {
+        // items before the one being changed are left alone so only later indices move
+        let mut shift = 0isize;
+        for &root in roots {
+            let len = self.list(list_type).items.len() as isize;
+            self.list(list_type).current_index = (root as isize + shift) as usize;
+            f(self);
+            shift += self.list(list_type).items.len() as isize - len;
+        }
+        let list = self.list(list_type);
+        list.current_index = roots.first().copied().unwrap_or(list.current_index);
+        list.fix_current_index();
+    }
+}
+
+/// The contents of a named list saved before a change so the change can be undone
+struct Snapshot {
+    list_index: usize,
+    list_type: ListType,
+    todo: Vec<TodoItem>,
+    todo_index: usize,
+    done: Vec<TodoItem>,
+    done_index: usize,
+}
+
+impl Snapshot {
+    fn changed(&self, named_list: &NamedList) -> bool {
+        self.todo != named_list.todo.items || self.done != named_list.done.items
+    }
+}
+
+struct TodoApp {
+    running: bool,
+    stdout: io::Stdout,
+    lists: Vec<NamedList>,
+    current_list: usize,
+    lists_dir: PathBuf,
+    picker_index: usize,
+    /// whether the sort menu sorts in reverse
+    sort_reverse: bool,
+    /// the items the sort menu sorts when it was opened from visual mode
+    sort_range: Option<Range<usize>>,
+    /// the tags shown in the tag picker and the ones picked
+    picker_tags: Vec<String>,
+    picked_tags: Vec<String>,
+    tag_index: usize,
+    list_type: ListType,
+    input_mode: InputMode,
+    input: LineEditor,
+    history: History,
+    /// whether the details of the current item are shown under the lists
+    show_details: bool,
+    /// the items of the current list after editing it in the editor, waiting to be confirmed
+    pending_edit: Option<Vec<TodoItem>>,
+    terminal_size: (u16, u16),
+    one_pane: bool,
+    clipboard: Clipboard,
+    repitition_modifier: Option<String>,
+    search: Option<String>,
+    /// where the cursor was when the search being typed was started
+    search_origin: usize,
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+    autosave: Autosave,
+    /// true if there are changes that haven't been saved
+    dirty: bool,
+    last_save: Instant,
+    /// last time the list files were checked for changes made by other processes
+    last_reload: Instant,
+    /// number of the signal that asked the program to exit, or 0 if there hasn't been one
+    signal: Arc<AtomicUsize>,
+}
+
+impl TodoApp {
+    fn new(args: &Args, signal: Arc<AtomicUsize>) -> io::Result<Self> {
+        let terminal_size = terminal::size().expect("Could not get terminal size");
+        let lists_dir = resolve_lists_dir(args);
+        let format = args.storage;
+        let mut lists = vec![NamedList::load(DEFAULT_LIST_NAME.to_string(), ListPaths::resolve(args), format)?];
+        for name in find_list_names(&lists_dir) {
+            let paths = ListPaths::named(&lists_dir, &name);
+            lists.push(NamedList::load(name, paths, format)?);
+        }
+        let current_list = match args.list.as_deref() {
+            None | Some(DEFAULT_LIST_NAME) => 0,
+            Some(name) => match lists.iter().position(|list| list.name == name) {
+                Some(index) => index,
+                None => {
+                    lists.push(NamedList::load(name.to_string(), ListPaths::named(&lists_dir, name), format)?);
+                    lists.len() - 1
+                }
+            },
+        };
+        Ok(Self {
+            running: true,
+            stdout: io::stdout(),
+            lists,
+            current_list,
+            lists_dir,
+            picker_index: current_list,
+            sort_reverse: false,
+            sort_range: None,
+            picker_tags: vec![],
+            picked_tags: vec![],
+            tag_index: 0,
+            list_type: ListType::Todo,
+            input_mode: InputMode::Normal,
+            input: LineEditor::new(),
+            history: History::load(args.history_file.clone().unwrap_or_else(|| default_data_dir().join(DEFAULT_HISTORY_FILE))),
+            show_details: false,
+            pending_edit: None,
+            terminal_size,
+            one_pane: terminal_size.0 <= MAX_WIDTH_SINGLE_PANE,
+            clipboard: Clipboard::new(args.clipboard, args.copy_command.clone(), args.paste_command.clone()),
+            repitition_modifier: None,
+            search: None,
+            search_origin: 0,
+            undo_stack: vec![],
+            redo_stack: vec![],
+            autosave: args.autosave,
+            dirty: false,
+            last_save: Instant::now(),
+            last_reload: Instant::now(),
+            signal,
+        })
+    }
+
+    fn go_to_current_index(&mut self) -> crossterm::Result<()> {
+        let size = self.pane_size();
+        let named_list = &self.lists[self.current_list];
+        match self.list_type {
+            ListType::Todo => named_list.todo.go_to_current_index(
+                (0, 0),
+                size,
+                &mut self.stdout,
             ),
-            ListType::Done => self.done.go_to_current_index(
+            ListType::Done => named_list.done.go_to_current_index(
                 if self.one_pane {
                     (0, 0)
                 } else {
@@ -446,81 +1409,460 @@ impl TodoApp {
         Ok(())
     }
 
+    /// searches and filters are typed on the bottom line so the lists stay visible
+    fn prompt_at_bottom(&self) -> bool {
+        matches!(
+            self.input_mode,
+            InputMode::Insert(InputDestination::Search | InputDestination::Filter) | InputMode::Priority | InputMode::Visual
+        )
+    }
+
+    /// rows taken up by the details pane
+    fn details_height(&self) -> u16 {
+        if self.show_details {
+            self.terminal_size.1 / 3
+        } else {
+            0
+        }
+    }
+
+    /// size of the area a single list is drawn in
+    fn pane_size(&self) -> (u16, u16) {
+        let height = if self.prompt_at_bottom() {
+            self.terminal_size.1.saturating_sub(1)
+        } else {
+            self.terminal_size.1
+        };
+        let height = height.saturating_sub(self.details_height());
+        if self.one_pane {
+            (self.terminal_size.0, height)
+        } else {
+            (self.terminal_size.0 / 2, height)
+        }
+    }
+
+    fn draw_lists(&mut self) -> crossterm::Result<()> {
+        if self.one_pane {
+            match self.list_type {
+                ListType::Todo => self.draw_todo()?,
+                ListType::Done => self.draw_done()?,
+            }
+        } else {
+            self.draw_todo()?;
+            self.draw_done()?;
+        }
+        if self.show_details {
+            self.draw_details()?;
+        }
+        Ok(())
+    }
+
+    /// draw the text, metadata and note of the current item under the lists
+    fn draw_details(&mut self) -> crossterm::Result<()> {
+        let y = self.pane_size().1;
+        let height = self.details_height() as usize;
+        let width = (self.terminal_size.0 as usize).max(1);
+        let item = self.selected_list().clone_current();
+        let mut lines: Vec<StyledContent<String>> = vec![];
+        match item {
+            Some(item) => {
+                lines.extend(word_wrap(&item.text, width).into_iter().map(|line| line.bold()));
+                let mut metadata = vec![];
+                if let Some(priority) = item.priority {
+                    metadata.push(format!("priority {}", priority));
+                }
+                if let Some(created) = item.created {
+                    metadata.push(format!("created {}", Date::from_timestamp(created)));
+                }
+                if let Some(completed) = item.completed {
+                    metadata.push(format!("completed {}", Date::from_timestamp(completed)));
+                }
+                if let Some(due) = item.due() {
+                    metadata.push(format!("due {}", due));
+                }
+                if !metadata.is_empty() {
+                    lines.extend(word_wrap(&metadata.join("  "), width).into_iter().map(|line| line.dark_grey()));
+                }
+                match item.note {
+                    Some(note) => {
+                        for line in note.lines() {
+                            match word_wrap(line, width) {
+                                wrapped if wrapped.is_empty() => lines.push(String::new().stylize()),
+                                wrapped => lines.extend(wrapped.into_iter().map(|line| line.stylize())),
+                            }
+                        }
+                    }
+                    None => lines.push("no note, press w to write one".to_string().dark_grey()),
+                }
+            }
+            None => lines.push("no item selected".to_string().dark_grey()),
+        }
+        queue!(
+            &mut self.stdout,
+            cursor::MoveTo(0, y),
+            PrintStyledContent("Details".yellow().bold()),
+        )?;
+        for (idx, line) in lines.into_iter().take(height.saturating_sub(1)).enumerate() {
+            queue!(
+                &mut self.stdout,
+                cursor::MoveTo(0, y + idx as u16 + 1),
+                PrintStyledContent(line),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// leave raw mode while `f` runs an editor
+    fn suspend<T>(&mut self, f: impl FnOnce() -> io::Result<T>) -> crossterm::Result<T> {
+        self.clear()?;
+        self.stdout.flush()?;
+        terminal::disable_raw_mode()?;
+        let res = f();
+        terminal::enable_raw_mode()?;
+        // the terminal may have been resized while the editor was open
+        self.terminal_size = terminal::size()?;
+        self.one_pane = self.terminal_size.0 <= MAX_WIDTH_SINGLE_PANE;
+        res
+    }
+
+    /// edit every item of the current list in the user's editor
+    /// the edit has to be confirmed if it removed any items
+    fn edit_list(&mut self) -> crossterm::Result<()> {
+        let items = self.selected_list().items.clone();
+        match self.suspend(|| editor::edit_items(&items))? {
+            Some(edited) if editor::diff_items(&items, &edited).0.is_empty() => {
+                self.selected_list().replace_items(edited);
+            }
+            Some(edited) => {
+                self.pending_edit = Some(edited);
+                self.input_mode = InputMode::ConfirmEdit;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn draw_confirm_edit(&mut self) -> crossterm::Result<()> {
+        let items = self.selected_list().items.clone();
+        let edited = self.pending_edit.clone().unwrap_or_default();
+        let (removed, added) = editor::diff_items(&items, &edited);
+        queue!(
+            &mut self.stdout,
+            cursor::MoveTo(0, 0),
+            PrintStyledContent("The edit removes the items marked with -, keep it? (y/n)".yellow().bold()),
+        )?;
+        let height = self.terminal_size.1.saturating_sub(1) as usize;
+        let lines = removed
+            .iter()
+            .map(|item| format!("- {}", item.text).red())
+            .chain(added.iter().map(|item| format!("+ {}", item.text).green()));
+        for (idx, line) in lines.take(height).enumerate() {
+            queue!(
+                &mut self.stdout,
+                cursor::MoveTo(0, idx as u16 + 1),
+                PrintStyledContent(line),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// edit the note of the current item in the user's editor, an empty note removes it
+    fn edit_note(&mut self) -> crossterm::Result<()> {
+        let note = match self.selected_list().clone_current() {
+            Some(item) => item.note.unwrap_or_default(),
+            None => return Ok(()),
+        };
+        if let Some(note) = self.suspend(|| editor::edit_text(&note, "note.md"))? {
+            if let Some(item) = self.selected_list().current_mut() {
+                item.note = Some(note.trim_end().to_string()).filter(|note| !note.is_empty());
+            }
+        }
+        Ok(())
+    }
+
     fn redraw(&mut self) -> crossterm::Result<()> {
         self.clear()?;
         match self.input_mode {
             InputMode::Normal => {
-                if self.one_pane {
-                    match self.list_type {
-                        ListType::Todo => self.draw_todo(),
-                        ListType::Done => self.draw_done(),
-                    }?
-                } else {
-                    self.draw_todo()?;
-                    self.draw_done()?;
-                }
+                self.draw_lists()?;
                 self.draw_repitition_modifier()?;
                 self.go_to_current_index()?;
             }
             InputMode::Insert(dest) => {
+                let y = if self.prompt_at_bottom() {
+                    self.draw_lists()?;
+                    self.terminal_size.1.saturating_sub(1)
+                } else {
+                    0
+                };
                 let leader = PrintStyledContent(match dest {
                     InputDestination::NewItem => "New item: ".blue().bold(),
                     InputDestination::NewItemBefore => "New item before current: ".magenta().bold(),
                     InputDestination::NewItemAfter => "New item after current: ".red().bold(),
                     InputDestination::EditItem => "Edit item: ".green().bold(),
+                    InputDestination::NewList => "New list name: ".yellow().bold(),
+                    InputDestination::Search => "/".blue().bold(),
+                    InputDestination::Filter => "Filter: ".magenta().bold(),
                 });
-                let input = self.input_string.clone(); // appease borrow checker
-                let idx = self.input_string_index; // appease borrow checker
+                let leader_width = leader.0.content().len();
+                let (input, column) = self.input.view((self.terminal_size.0 as usize).saturating_sub(leader_width));
+                let input = input.to_string(); // appease borrow checker
                 queue!(
                     &mut self.stdout,
+                    cursor::MoveTo(0, y),
                     leader,
                     Print(input),
                     cursor::MoveTo(
-                        leader.0.content().len() as u16 + idx as u16,
-                        0
+                        leader_width as u16 + column as u16,
+                        y
                     ),
                 )?;
             }
+            InputMode::ListPicker => self.draw_list_picker()?,
+            InputMode::TagPicker => self.draw_tag_picker()?,
+            InputMode::SortMenu => self.draw_sort_menu()?,
+            InputMode::ConfirmEdit => self.draw_confirm_edit()?,
+            InputMode::Priority => {
+                self.draw_lists()?;
+                let y = self.terminal_size.1.saturating_sub(1);
+                queue!(
+                    &mut self.stdout,
+                    cursor::MoveTo(0, y),
+                    PrintStyledContent("Priority: ".yellow().bold()),
+                    Print("+/- raise or lower, 1-5 set, 0 clear, Enter done"),
+                )?;
+                self.go_to_current_index()?;
+            }
+            InputMode::Visual => {
+                self.draw_lists()?;
+                let y = self.terminal_size.1.saturating_sub(1);
+                queue!(
+                    &mut self.stdout,
+                    cursor::MoveTo(0, y),
+                    PrintStyledContent("-- VISUAL --".yellow().bold()),
+                )?;
+                self.go_to_current_index()?;
+            }
         }
         self.stdout.flush()
     }
 
+    fn draw_sort_menu(&mut self) -> crossterm::Result<()> {
+        let last_sort = self.selected_list().last_sort;
+        queue!(
+            &mut self.stdout,
+            cursor::MoveTo(0, 0),
+            PrintStyledContent("Sort by".yellow().bold()),
+        )?;
+        for (idx, (ch, key)) in SortKey::ALL.iter().enumerate() {
+            let marker = if last_sort.map(|sort| sort.key) == Some(*key) { "* " } else { "  " };
+            queue!(
+                &mut self.stdout,
+                cursor::MoveTo(0, idx as u16 + 1),
+                Print(marker),
+                PrintStyledContent(ch.bold()),
+                Print(format!("  {}", key.name())),
+            )?;
+        }
+        let y = SortKey::ALL.len() as u16 + 2;
+        let reverse = if self.sort_reverse { "on" } else { "off" };
+        queue!(
+            &mut self.stdout,
+            cursor::MoveTo(2, y),
+            PrintStyledContent('r'.bold()),
+            Print(format!("  reverse: {}", reverse)),
+        )?;
+
+        if let Some(sort) = last_sort {
+            queue!(
+                &mut self.stdout,
+                cursor::MoveTo(2, y + 1),
+                PrintStyledContent('s'.bold()),
+                Print(format!("  sort by {} again", sort.key.name())),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw_list_picker(&mut self) -> crossterm::Result<()> {
+        queue!(
+            &mut self.stdout,
+            cursor::MoveTo(0, 0),
+            PrintStyledContent("Lists".yellow().bold()),
+        )?;
+        let height = self.terminal_size.1.saturating_sub(1) as usize;
+        let offset = (self.picker_index + 1).saturating_sub(height);
+        for (idx, named_list) in self.lists.iter().enumerate().skip(offset).take(height) {
+            let marker = if idx == self.current_list { "* " } else { "  " };
+            let counts = format!(" ({}/{})", named_list.todo.items.len(), named_list.done.items.len());
+            queue!(
+                &mut self.stdout,
+                cursor::MoveTo(0, (idx - offset + 1) as u16),
+                Print(marker),
+                PrintStyledContent(named_list.name.as_str().with(color(idx))),
+                Print(counts),
+            )?;
+        }
+        let y = (self.picker_index - offset + 1) as u16;
+        queue!(&mut self.stdout, cursor::MoveTo(2, y))
+    }
+
+    fn draw_tag_picker(&mut self) -> crossterm::Result<()> {
+        queue!(
+            &mut self.stdout,
+            cursor::MoveTo(0, 0),
+            PrintStyledContent("Tags".yellow().bold()),
+        )?;
+        if self.picker_tags.is_empty() {
+            return queue!(
+                &mut self.stdout,
+                cursor::MoveTo(0, 1),
+                PrintStyledContent("no items have tags".dark_grey()),
+            );
+        }
+        let height = self.terminal_size.1.saturating_sub(1) as usize;
+        let offset = (self.tag_index + 1).saturating_sub(height);
+        for (idx, tag) in self.picker_tags.iter().enumerate().skip(offset).take(height) {
+            let checkbox = if self.picked_tags.contains(tag) { "[x] " } else { "[ ] " };
+            queue!(
+                &mut self.stdout,
+                cursor::MoveTo(0, (idx - offset + 1) as u16),
+                Print(checkbox),
+                PrintStyledContent(tag.as_str().with(color(idx)).bold()),
+            )?;
+        }
+        let y = (self.tag_index - offset + 1) as u16;
+        queue!(&mut self.stdout, cursor::MoveTo(1, y))
+    }
+
     fn run(&mut self) -> crossterm::Result<()> {
+        // save even if drawing fails, for example when the terminal goes away
+        let res = self.event_loop();
+        self.save()?;
+        res?;
+        self.clear()
+    }
+
+    fn event_loop(&mut self) -> crossterm::Result<()> {
         self.redraw()?;
         while self.running {
+            if self.signal.load(Ordering::Relaxed) != 0 {
+                break;
+            }
+            if self.last_reload.elapsed() >= RELOAD_INTERVAL {
+                self.last_reload = Instant::now();
+                if self.reload()? {
+                    self.redraw()?;
+                }
+            }
             if self.kbin()? {
                 self.redraw()?;
             }
+            self.autosave()?;
         }
-        save_list(TODO_LIST, &self.todo.items);
-        save_list(DONE_LIST, &self.done.items);
-        self.clear()
+        Ok(())
+    }
+
+    /// save every list, even if saving one of them fails
+    fn save(&mut self) -> crossterm::Result<()> {
+        let mut res = Ok(());
+        for index in 0..self.lists.len() {
+            let (todo, done) = (self.lists[index].todo.items.clone(), self.lists[index].done.items.clone());
+            if let Err(err) = self.lists[index].save() {
+                res = res.and(Err(err));
+            }
+            self.rebase_history(index, &todo, &done);
+        }
+        self.leave_lost_selection();
+        res?;
+        self.dirty = false;
+        self.last_save = Instant::now();
+        Ok(())
+    }
+
+    /// pick up changes other processes made to any of the lists
+    /// returns true if anything changed
+    fn reload(&mut self) -> io::Result<bool> {
+        let mut changed = false;
+        for index in 0..self.lists.len() {
+            let (todo, done) = (self.lists[index].todo.items.clone(), self.lists[index].done.items.clone());
+            if self.lists[index].reload()? {
+                changed = true;
+                self.rebase_history(index, &todo, &done);
+            }
+        }
+        self.leave_lost_selection();
+        Ok(changed)
+    }
+
+    /// apply changes merged in from other processes since a list held `todo` and `done` to its undo history
+    /// and to an edit waiting to be confirmed, so going back to them doesn't throw those changes away.
+    /// sorting a range picked before the changes is given up on
+    fn rebase_history(&mut self, list_index: usize, todo: &[TodoItem], done: &[TodoItem]) {
+        let named_list = &self.lists[list_index];
+        if named_list.todo.items == todo && named_list.done.items == done {
+            return;
+        }
+        // a range picked to be sorted may not hold the same items anymore
+        if list_index == self.current_list && self.sort_range.take().is_some() {
+            self.input_mode = InputMode::Normal;
+        }
+        let snapshots = self.undo_stack.iter_mut().chain(&mut self.redo_stack);
+        for snapshot in snapshots.filter(|snapshot| snapshot.list_index == list_index) {
+            snapshot.todo = storage::merge_items(todo, &snapshot.todo, &named_list.todo.items);
+            snapshot.done = storage::merge_items(done, &snapshot.done, &named_list.done.items);
+        }
+        if let (Some(edited), true) = (&mut self.pending_edit, list_index == self.current_list) {
+            *edited = match self.list_type {
+                ListType::Todo => storage::merge_items(todo, edited, &named_list.todo.items),
+                ListType::Done => storage::merge_items(done, edited, &named_list.done.items),
+            };
+        }
+    }
+
+    /// go back to normal mode if merging in changes from other processes cleared the visual selection
+    fn leave_lost_selection(&mut self) {
+        if matches!(self.input_mode, InputMode::Visual) && self.selected_list().anchor.is_none() {
+            self.input_mode = InputMode::Normal;
+        }
+    }
+
+    fn autosave(&mut self) -> crossterm::Result<()> {
+        let due = match self.autosave {
+            Autosave::Off => false,
+            Autosave::OnChange => true,
+            Autosave::Interval(interval) => self.last_save.elapsed() >= interval,
+        };
+        if self.dirty && due {
+            self.save()?;
+        }
+        Ok(())
     }
 
     fn draw_todo(&mut self) -> crossterm::Result<()> {
-        self.todo.draw(
+        let size = self.pane_size();
+        let named_list = &mut self.lists[self.current_list];
+        named_list.todo.draw(
+            &named_list.name,
+            self.search.as_deref(),
             (0, 0),
-            if self.one_pane {
-                self.terminal_size
-            } else {
-                (self.terminal_size.0 / 2, self.terminal_size.1)
-            },
+            size,
             &mut self.stdout,
         )
     }
 
     fn draw_done(&mut self) -> crossterm::Result<()> {
-        self.done.draw(
+        let size = self.pane_size();
+        let named_list = &mut self.lists[self.current_list];
+        named_list.done.draw(
+            &named_list.name,
+            self.search.as_deref(),
             if self.one_pane {
                 (0, 0)
             } else {
                 (self.terminal_size.0 / 2, 0)
             },
-            if self.one_pane {
-                self.terminal_size
-            } else {
-                (self.terminal_size.0 / 2, self.terminal_size.1)
-            },
+            size,
             &mut self.stdout,
         )
     }
@@ -537,35 +1879,201 @@ impl TodoApp {
         self.list_type = self.list_type.next();
     }
 
-    fn check_item(&mut self) {
-        if let Some(item) = self.todo.remove() {
-            self.done.add(item);
+    fn named_list(&mut self) -> &mut NamedList {
+        &mut self.lists[self.current_list]
+    }
+
+    /// the list the cursor is in
+    fn selected_list(&mut self) -> &mut List {
+        let named_list = &mut self.lists[self.current_list];
+        match self.list_type {
+            ListType::Todo => &mut named_list.todo,
+            ListType::Done => &mut named_list.done,
         }
     }
 
-    fn uncheck_item(&mut self) {
-        if let Some(item) = self.done.remove() {
-            self.todo.add(item);
+    /// only show items with one of `tags` in both lists of the current named list
+    fn set_tags(&mut self, tags: Vec<String>) {
+        let named_list = self.named_list();
+        named_list.todo.set_tags(tags.clone());
+        named_list.done.set_tags(tags);
+    }
+
+    /// filter both lists of the current named list
+    fn set_filter(&mut self, filter: Option<String>) {
+        let named_list = self.named_list();
+        named_list.todo.set_filter(filter.clone());
+        named_list.done.set_filter(filter);
+    }
+
+    fn select_list(&mut self, index: usize) {
+        self.current_list = index;
+        self.picker_index = index;
+    }
+
+    fn next_list(&mut self) {
+        self.select_list((self.current_list + 1) % self.lists.len());
+    }
+
+    fn prev_list(&mut self) {
+        self.select_list((self.current_list + self.lists.len() - 1) % self.lists.len());
+    }
+
+    fn create_list(&mut self, name: String) -> io::Result<()> {
+        if let Some(index) = self.lists.iter().position(|list| list.name == name) {
+            self.select_list(index);
+        } else if valid_list_name(&name) {
+            let paths = ListPaths::named(&self.lists_dir, &name);
+            let format = self.lists[0].format;
+            self.lists.push(NamedList::load(name, paths, format)?);
+            self.select_list(self.lists.len() - 1);
         }
+        Ok(())
+    }
+
+    fn check_item(&mut self) {
+        self.named_list().check_item();
+    }
+
+    fn uncheck_item(&mut self) {
+        self.named_list().uncheck_item();
     }
 
     fn delete_item(&mut self) {
-        self.done.remove();
+        self.named_list().delete_item();
+    }
+
+    /// run `f` with the cursor on each item selected in visual mode, from the top down, then leave visual mode
+    fn for_each_selected(&mut self, f: fn(&mut NamedList)) {
+        let roots = self.selected_list().selected_roots();
+        let list_type = self.list_type;
+        self.named_list().for_each_root(list_type, &roots, f);
+        self.selected_list().anchor = None;
+        self.input_mode = InputMode::Normal;
+    }
+
+    /// run `f` with the cursor on each marked item, from the top down
+    fn for_each_marked(&mut self, f: fn(&mut NamedList)) {
+        let roots = self.selected_list().marked_roots();
+        let list_type = self.list_type;
+        self.named_list().for_each_root(list_type, &roots, f);
+    }
+
+    fn exit_visual(&mut self) {
+        let list = self.selected_list();
+        if let Some(selection) = list.selection() {
+            list.current_index = selection.start;
+        }
+        list.anchor = None;
+        list.fix_current_index();
+        self.input_mode = InputMode::Normal;
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let named_list = &self.lists[self.current_list];
+        Snapshot {
+            list_index: self.current_list,
+            list_type: self.list_type,
+            todo: named_list.todo.items.clone(),
+            todo_index: named_list.todo.current_index,
+            done: named_list.done.items.clone(),
+            done_index: named_list.done.current_index,
+        }
+    }
+
+    /// put a named list back the way it was in `snapshot`, returning a snapshot of how it was before
+    fn restore(&mut self, snapshot: Snapshot) -> Snapshot {
+        self.select_list(snapshot.list_index);
+        let current = self.snapshot();
+        self.list_type = snapshot.list_type;
+        let named_list = self.named_list();
+        named_list.todo.items = snapshot.todo;
+        named_list.todo.current_index = snapshot.todo_index;
+        named_list.done.items = snapshot.done;
+        named_list.done.current_index = snapshot.done_index;
+        // other processes may have removed items since the snapshot was taken
+        named_list.todo.fix_current_index();
+        named_list.done.fix_current_index();
+        current
+    }
+
+    fn undo(&mut self) {
+        if let Some(snapshot) = self.undo_stack.pop() {
+            let current = self.restore(snapshot);
+            self.redo_stack.push(current);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(snapshot) = self.redo_stack.pop() {
+            let current = self.restore(snapshot);
+            self.undo_stack.push(current);
+        }
+    }
+
+    /// handle the undo and redo keys, which have to be dealt with before changes are recorded
+    /// returns true if the event was handled
+    fn handle_history(&mut self, evnt: &Event) -> bool {
+        if !matches!(self.input_mode, InputMode::Normal) {
+            return false;
+        }
+        match evnt {
+            Event::Key(KeyEvent { code: KeyCode::Char('u'), .. }) => {
+                for _ in 0..use_repitition(&mut self.repitition_modifier) {
+                    self.undo();
+                }
+            }
+            Event::Key(KeyEvent { code: KeyCode::Char('r'), modifiers }) if modifiers.contains(KeyModifiers::CONTROL) => {
+                for _ in 0..use_repitition(&mut self.repitition_modifier) {
+                    self.redo();
+                }
+            }
+            _ => return false,
+        }
+        true
     }
 
     /// handle keyboard input
     /// returns Ok(true) if redraw needs to be called again, otherwise returns Ok(false)
     fn kbin(&mut self) -> crossterm::Result<bool> {
+        if event::poll(Duration::from_millis(50))? {
+            let evnt = event::read()?;
+            if self.handle_history(&evnt) {
+                self.dirty = true;
+                self.named_list().clear_marks();
+                return Ok(true);
+            }
+            // every change made by a single event, repeated or not, is undone as one step
+            let before = self.snapshot();
+            let res = self.handle_event(evnt);
+            if before.changed(&self.lists[before.list_index]) {
+                self.dirty = true;
+                // marks are indices so they can't be trusted once the items change
+                self.lists[before.list_index].clear_marks();
+                self.undo_stack.push(before);
+                if self.undo_stack.len() > UNDO_LIMIT {
+                    self.undo_stack.remove(0);
+                }
+                self.redo_stack.clear();
+            }
+            return res;
+        }
+        Ok(true)
+    }
+
+    /// handle a single input event
+    /// returns Ok(true) if redraw needs to be called again, otherwise returns Ok(false)
+    fn handle_event(&mut self, evnt: Event) -> crossterm::Result<bool> {
         macro_rules! repeat {
             ($stmt:stmt) => (
                 for _ in 0..use_repitition(&mut self.repitition_modifier) { $stmt }
             )
         }
-        if event::poll(Duration::from_millis(50))? {
-            let evnt = event::read()?;
+        {
+            let named_list = &mut self.lists[self.current_list];
             let list = match self.list_type {
-                ListType::Todo => &mut self.todo,
-                ListType::Done => &mut self.done,
+                ListType::Todo => &mut named_list.todo,
+                ListType::Done => &mut named_list.done,
             };
             match self.input_mode {
                 InputMode::Normal => match evnt {
@@ -573,13 +2081,30 @@ impl TodoApp {
                         self.terminal_size = (w, h);
                         self.one_pane = self.terminal_size.0 <= MAX_WIDTH_SINGLE_PANE;
                     }
+                    Event::Key(KeyEvent { code: KeyCode::Char('e'), modifiers }) if modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.edit_list()?;
+                    }
                     Event::Key(key_event) => match (key_event.code, self.list_type) {
-                        (KeyCode::Char('q') | KeyCode::Esc, _) => if self.repitition_modifier.take() == None {
+                        (KeyCode::Char('q') | KeyCode::Esc, _) => if self.repitition_modifier.take().is_none() {
                             self.running = false;
                         },
-                        (KeyCode::Char('d') | KeyCode::Char('x') | KeyCode::Enter, ListType::Todo) => repeat! { self.check_item() },
-                        (KeyCode::Char('x') | KeyCode::Enter, ListType::Done) => repeat! { self.uncheck_item() },
-                        (KeyCode::Char('d') | KeyCode::Backspace, ListType::Done) => repeat! { self.delete_item() },
+                        (KeyCode::Char('d') | KeyCode::Char('x') | KeyCode::Enter, ListType::Todo) => if list.marked.is_empty() {
+                            repeat! { self.check_item() }
+                        } else {
+                            self.for_each_marked(NamedList::check_item)
+                        },
+                        (KeyCode::Char('x') | KeyCode::Enter, ListType::Done) => if list.marked.is_empty() {
+                            repeat! { self.uncheck_item() }
+                        } else {
+                            self.for_each_marked(NamedList::uncheck_item)
+                        },
+                        (KeyCode::Char('d') | KeyCode::Backspace, ListType::Done) => if list.marked.is_empty() {
+                            repeat! { self.delete_item() }
+                        } else {
+                            self.for_each_marked(NamedList::delete_item)
+                        },
+                        (KeyCode::Tab, _) => self.next_list(),
+                        (KeyCode::BackTab, _) => self.prev_list(),
                         (KeyCode::Char('O'), ListType::Todo) => self.input_mode = InputMode::Insert(InputDestination::NewItemBefore),
                         (KeyCode::Char('o'), ListType::Todo) => self.input_mode = InputMode::Insert(InputDestination::NewItemAfter),
                         (KeyCode::Char(ch), _) => match ch {
@@ -590,101 +2115,401 @@ impl TodoApp {
                             'e' | 'E' => {
                                 self.input_mode = InputMode::Insert(InputDestination::EditItem);
                                 if let Some(item) = list.clone_current() {
-                                    self.input_string_index = if ch == 'E' {
-                                        0
-                                    } else {
-                                        item.len()
-                                    };
-                                    self.input_string = item;
+                                    self.input.set(item.text, ch == 'e');
                                 }
                             }
                             'a' | 'i' => self.input_mode = InputMode::Insert(InputDestination::NewItem),
                             'h' | 'l' => self.swap_list(),
+                            '/' => {
+                                self.search_origin = list.current_index;
+                                self.input_mode = InputMode::Insert(InputDestination::Search);
+                            }
+                            'n' => if let Some(pattern) = &self.search {
+                                repeat! { list.jump_to_match(pattern, true, false) }
+                            },
+                            'N' => if let Some(pattern) = &self.search {
+                                repeat! { list.jump_to_match(pattern, false, false) }
+                            },
+                            'f' => {
+                                self.input.set(list.filter.clone().unwrap_or_default(), true);
+                                self.input_mode = InputMode::Insert(InputDestination::Filter);
+                            }
+                            'F' => {
+                                self.set_filter(None);
+                                self.set_tags(vec![]);
+                            }
+                            't' => {
+                                let named_list = &self.lists[self.current_list];
+                                let mut tags = named_list.todo.all_tags();
+                                tags.extend(named_list.done.all_tags());
+                                tags.sort_unstable();
+                                tags.dedup();
+                                self.picker_tags = tags;
+                                self.picked_tags = named_list.todo.tags.clone();
+                                self.tag_index = 0;
+                                self.input_mode = InputMode::TagPicker;
+                            }
+                            'L' => {
+                                self.picker_index = self.current_list;
+                                self.input_mode = InputMode::ListPicker;
+                            }
                             'j' => repeat! { list.move_down() },
                             'J' => repeat! { list.shift_down() },
                             'k' => repeat! { list.move_up() },
                             'K' => repeat! { list.shift_up() },
                             'g' => repeat! { list.move_to_top() },
                             'G' => repeat! { list.move_to_bottom() },
-                            's' => repeat! { list.sort() },
-                            // TODO: add ability to copy multiple lines
+                            's' => {
+                                self.sort_reverse = list.last_sort.is_some_and(|sort| sort.reverse);
+                                self.input_mode = InputMode::SortMenu;
+                            }
+                            'v' => self.show_details = !self.show_details,
+                            'w' => self.edit_note()?,
+                            'V' => if list.has_current() {
+                                list.anchor = Some(list.current_index);
+                                self.input_mode = InputMode::Visual;
+                            },
+                            'D' => list.sort(Sort::new(SortKey::Due)),
+                            'S' => list.sort(Sort::new(SortKey::Priority)),
+                            '!' => if list.has_current() {
+                                self.input_mode = InputMode::Priority;
+                            },
+                            'y' if !list.marked.is_empty() => self.clipboard.copy(list.clone_marked(), &mut self.stdout)?,
                             'y' => {
-                                self.clipboard = vec![];
+                                let mut items = vec![];
                                 let index = list.current_index;
                                 for _ in 0..use_repitition(&mut self.repitition_modifier) {
-                                    self.clipboard.append(&mut list.clone_current().into_iter().collect());
+                                    if !list.has_current() {
+                                        break;
+                                    }
+                                    items.extend(list.clone_subtree());
+                                    // skip over the subtasks that were copied along with the item
+                                    list.current_index = list.subtree_end(list.current_index) - 1;
                                     list.move_down();
                                 }
                                 list.current_index = index;
+                                self.clipboard.copy(items, &mut self.stdout)?;
                             },
-                            'p' => repeat! { list.insert_many_after(&self.clipboard) },
-                            'P' => repeat! { list.insert_many_before(&self.clipboard) },
+                            'p' => {
+                                let items = self.clipboard.paste();
+                                repeat! { list.insert_after(&items) }
+                            }
+                            'P' => {
+                                let items = self.clipboard.paste();
+                                repeat! { list.insert_before(&items) }
+                            }
+                            'm' | ' ' => list.toggle_mark(),
+                            'M' => list.marked.clear(),
+                            '>' => list.indent(),
+                            '<' => list.outdent(),
+                            'z' => list.toggle_collapsed(),
                             _ => return Ok(false),
                         }
                         _ => return Ok(false),
                     }
                     _ => return Ok(false),
                 },
-                InputMode::Insert(dest) => match evnt {
+                InputMode::ConfirmEdit => match evnt {
                     Event::Key(key_event) => match key_event.code {
-                        KeyCode::Left => if self.input_string_index >= 1 {
-                            self.input_string_index -= 1;
-                        },
-                        KeyCode::Right => {
-                            let len = self.input_string.len();
-                            self.input_string_index += 1;
-                            if self.input_string_index > len {
-                                self.input_string_index = len;
+                        KeyCode::Char('y') => {
+                            if let Some(edited) = self.pending_edit.take() {
+                                list.replace_items(edited);
                             }
+                            self.input_mode = InputMode::Normal;
                         }
-                        KeyCode::Esc => {
+                        KeyCode::Char('n') | KeyCode::Char('q') | KeyCode::Esc => {
+                            self.pending_edit = None;
                             self.input_mode = InputMode::Normal;
-                            self.input_string = "".to_string();
-                            self.input_string_index = 0;
                         }
-                        KeyCode::Delete => if self.input_string.len() > self.input_string_index {
-                            self.input_string.remove(self.input_string_index);
+                        _ => return Ok(false),
+                    },
+                    _ => return Ok(false),
+                },
+                InputMode::Priority => match evnt {
+                    Event::Key(key_event) => {
+                        let item = match list.current_mut() {
+                            Some(item) => item,
+                            None => {
+                                self.input_mode = InputMode::Normal;
+                                return Ok(true);
+                            }
+                        };
+                        match key_event.code {
+                            KeyCode::Char('+') | KeyCode::Char('k') | KeyCode::Up => item.raise_priority(),
+                            KeyCode::Char('-') | KeyCode::Char('j') | KeyCode::Down => item.lower_priority(),
+                            KeyCode::Char(ch @ '1'..='5') => {
+                                item.priority = Some((b'A' + (ch as u8 - b'1')) as char);
+                                self.input_mode = InputMode::Normal;
+                            }
+                            KeyCode::Char('0') | KeyCode::Backspace | KeyCode::Delete => {
+                                item.priority = None;
+                                self.input_mode = InputMode::Normal;
+                            }
+                            KeyCode::Enter | KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('!') => {
+                                self.input_mode = InputMode::Normal;
+                            }
+                            _ => return Ok(false),
                         }
-                        KeyCode::Backspace => {
-                            if !self.input_string.is_empty() {
-                                if self.input_string_index >= 1 {
-                                    self.input_string_index -= 1;
+                    }
+                    _ => return Ok(false),
+                },
+                InputMode::SortMenu => match evnt {
+                    Event::Key(key_event) => match key_event.code {
+                        KeyCode::Char('q') | KeyCode::Esc => {
+                            self.sort_range = None;
+                            self.input_mode = InputMode::Normal;
+                        }
+                        KeyCode::Char('r') => self.sort_reverse = !self.sort_reverse,
+                        KeyCode::Char('s') | KeyCode::Enter => {
+                            let range = self.sort_range.take().unwrap_or(0..list.items.len());
+                            if let Some(sort) = list.last_sort {
+                                list.sort_range(Sort { reverse: self.sort_reverse, ..sort }, range);
+                            }
+                            self.input_mode = InputMode::Normal;
+                        }
+                        KeyCode::Char(ch) => match SortKey::from_char(ch) {
+                            Some(key) => {
+                                let range = self.sort_range.take().unwrap_or(0..list.items.len());
+                                list.sort_range(Sort { key, reverse: self.sort_reverse }, range);
+                                self.input_mode = InputMode::Normal;
+                            }
+                            None => return Ok(false),
+                        },
+                        _ => return Ok(false),
+                    },
+                    _ => return Ok(false),
+                },
+                InputMode::Visual => match evnt {
+                    Event::Key(key_event) => match (key_event.code, self.list_type) {
+                        (KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('V'), _) => self.exit_visual(),
+                        (KeyCode::Char(ch @ '0'..='9'), _) => match &mut self.repitition_modifier {
+                            Some(string) => string.push(ch),
+                            None => self.repitition_modifier = Some(ch.to_string()),
+                        },
+                        (KeyCode::Char('j') | KeyCode::Down, _) => repeat! { list.move_down() },
+                        (KeyCode::Char('k') | KeyCode::Up, _) => repeat! { list.move_up() },
+                        (KeyCode::Char('g'), _) => list.move_to_top(),
+                        (KeyCode::Char('G'), _) => list.move_to_bottom(),
+                        (KeyCode::Char('J'), _) => repeat! { list.shift_selection(false) },
+                        (KeyCode::Char('K'), _) => repeat! { list.shift_selection(true) },
+                        (KeyCode::Char('y'), _) => {
+                            self.clipboard.copy(list.clone_selection(), &mut self.stdout)?;
+                            self.exit_visual();
+                        }
+                        (KeyCode::Char('d') | KeyCode::Char('x') | KeyCode::Enter, ListType::Todo) => {
+                            self.for_each_selected(NamedList::check_item);
+                        }
+                        (KeyCode::Char('x') | KeyCode::Enter, ListType::Done) => self.for_each_selected(NamedList::uncheck_item),
+                        (KeyCode::Char('d') | KeyCode::Backspace, ListType::Done) => self.for_each_selected(NamedList::delete_item),
+                        // sorting items from different levels together would move them to other parents
+                        (KeyCode::Char('s'), _) if list.level_selection().is_some() => {
+                            self.sort_range = list.level_selection();
+                            self.sort_reverse = list.last_sort.is_some_and(|sort| sort.reverse);
+                            self.exit_visual();
+                            self.input_mode = InputMode::SortMenu;
+                        }
+                        _ => return Ok(false),
+                    },
+                    _ => return Ok(false),
+                },
+                InputMode::TagPicker => match evnt {
+                    Event::Key(key_event) => match key_event.code {
+                        KeyCode::Char('q') | KeyCode::Esc => self.input_mode = InputMode::Normal,
+                        KeyCode::Char('j') | KeyCode::Down if !self.picker_tags.is_empty() => {
+                            self.tag_index = (self.tag_index + 1) % self.picker_tags.len();
+                        }
+                        KeyCode::Char('k') | KeyCode::Up if !self.picker_tags.is_empty() => {
+                            self.tag_index = (self.tag_index + self.picker_tags.len() - 1) % self.picker_tags.len();
+                        }
+                        KeyCode::Char('g') => self.tag_index = 0,
+                        KeyCode::Char('G') => self.tag_index = self.picker_tags.len().saturating_sub(1),
+                        KeyCode::Char(' ') => if let Some(tag) = self.picker_tags.get(self.tag_index) {
+                            match self.picked_tags.iter().position(|picked| picked == tag) {
+                                Some(index) => {
+                                    self.picked_tags.remove(index);
                                 }
-                                self.input_string.remove(self.input_string_index);
+                                None => self.picked_tags.push(tag.clone()),
                             }
+                        },
+                        KeyCode::Char('c') => {
+                            self.set_tags(vec![]);
+                            self.input_mode = InputMode::Normal;
                         }
                         KeyCode::Enter => {
+                            // picking a tag with Enter when none are checked shows just that tag
+                            if self.picked_tags.is_empty() {
+                                self.picked_tags.extend(self.picker_tags.get(self.tag_index).cloned());
+                            }
+                            self.set_tags(self.picked_tags.clone());
+                            self.input_mode = InputMode::Normal;
+                        }
+                        _ => return Ok(false),
+                    },
+                    _ => return Ok(false),
+                },
+                InputMode::ListPicker => match evnt {
+                    Event::Key(key_event) => match key_event.code {
+                        KeyCode::Char('q') | KeyCode::Esc => self.input_mode = InputMode::Normal,
+                        KeyCode::Char('j') | KeyCode::Down => self.picker_index = (self.picker_index + 1) % self.lists.len(),
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            self.picker_index = (self.picker_index + self.lists.len() - 1) % self.lists.len();
+                        }
+                        KeyCode::Char('g') => self.picker_index = 0,
+                        KeyCode::Char('G') => self.picker_index = self.lists.len() - 1,
+                        KeyCode::Char('a') | KeyCode::Char('i') => self.input_mode = InputMode::Insert(InputDestination::NewList),
+                        KeyCode::Enter | KeyCode::Char('l') => {
+                            self.select_list(self.picker_index);
+                            self.input_mode = InputMode::Normal;
+                        }
+                        _ => return Ok(false),
+                    },
+                    _ => return Ok(false),
+                },
+                InputMode::Insert(dest) => match evnt {
+                    Event::Key(KeyEvent { code, modifiers }) if modifiers.contains(KeyModifiers::CONTROL) => match code {
+                        KeyCode::Char('a') => self.input.home(),
+                        KeyCode::Char('e') => self.input.end(),
+                        KeyCode::Char('b') => self.input.left(),
+                        KeyCode::Char('f') => self.input.right(),
+                        KeyCode::Left => self.input.word_left(),
+                        KeyCode::Right => self.input.word_right(),
+                        KeyCode::Char('h') => self.input.backspace(),
+                        KeyCode::Char('d') => self.input.delete(),
+                        KeyCode::Char('w') => self.input.kill_whitespace_word_back(),
+                        KeyCode::Char('u') => self.input.kill_to_start(),
+                        KeyCode::Char('k') => self.input.kill_to_end(),
+                        KeyCode::Char('y') => self.input.yank(),
+                        _ => return Ok(false),
+                    },
+                    Event::Key(KeyEvent { code, modifiers }) if modifiers.contains(KeyModifiers::ALT) => match code {
+                        KeyCode::Char('b') | KeyCode::Left => self.input.word_left(),
+                        KeyCode::Char('f') | KeyCode::Right => self.input.word_right(),
+                        KeyCode::Char('d') => self.input.kill_word_forward(),
+                        KeyCode::Backspace => self.input.kill_word_back(),
+                        KeyCode::Char('y') => self.input.yank_pop(),
+                        _ => return Ok(false),
+                    },
+                    Event::Key(key_event) => match key_event.code {
+                        KeyCode::Left => self.input.left(),
+                        KeyCode::Right => self.input.right(),
+                        KeyCode::Home => self.input.home(),
+                        KeyCode::End => self.input.end(),
+                        KeyCode::Up => self.input.browse_history(&self.history.entries(dest.history_kind()), true),
+                        KeyCode::Down => self.input.browse_history(&self.history.entries(dest.history_kind()), false),
+                        KeyCode::Tab | KeyCode::BackTab => {
+                            let named_list = &self.lists[self.current_list];
+                            self.input.complete(|prefix| named_list.completions(prefix), key_event.code == KeyCode::Tab);
+                        }
+                        KeyCode::Esc => {
                             self.input_mode = InputMode::Normal;
-                            self.input_string_index = 0;
-                            let s = std::mem::take(&mut self.input_string);
+                            self.input.take();
                             match dest {
-                                InputDestination::NewItem => repeat! { self.todo.add(s.clone()) },
-                                InputDestination::NewItemBefore => repeat! { self.todo.insert_before(s.clone()) },
-                                InputDestination::NewItemAfter => repeat! { self.todo.insert_after(s.clone()) },
-                                InputDestination::EditItem => list.set_current(s),
+                                InputDestination::Search => {
+                                    self.search = None;
+                                    list.current_index = self.search_origin;
+                                }
+                                InputDestination::Filter => self.set_filter(None),
+                                _ => {}
                             }
                         }
-                        KeyCode::Char(ch) => {
-                            self.input_string.insert(self.input_string_index, ch);
-                            self.input_string_index += 1;
+                        KeyCode::Delete => self.input.delete(),
+                        KeyCode::Backspace => self.input.backspace(),
+                        KeyCode::Enter => {
+                            self.input_mode = InputMode::Normal;
+                            let s = self.input.take();
+                            match dest {
+                                InputDestination::NewItem => repeat! { self.named_list().todo.add(&[TodoItem::new(s.clone())]) },
+                                InputDestination::NewItemBefore => repeat! { self.named_list().todo.insert_before(&[TodoItem::new(s.clone())]) },
+                                InputDestination::NewItemAfter => repeat! { self.named_list().todo.insert_after(&[TodoItem::new(s.clone())]) },
+                                InputDestination::EditItem => list.set_current_text(s.clone()),
+                                InputDestination::NewList => self.create_list(s.clone())?,
+                                InputDestination::Search => self.search = Some(s.clone()).filter(|s| !s.is_empty()),
+                                InputDestination::Filter => self.set_filter(Some(s.clone())),
+                            }
+                            // the history is only a convenience, so not being able to save it isn't worth stopping for
+                            let _ = self.history.add(dest.history_kind(), &s);
                         }
+                        KeyCode::Char(ch) => self.input.insert(ch),
                         _ => return Ok(false),
                     },
                     _ => return Ok(false),
                 }
             }
         }
+        if let InputMode::Insert(dest) = self.input_mode {
+            self.preview_input(dest);
+        }
         Ok(true)
     }
+
+    /// apply searches and filters while they are being typed
+    fn preview_input(&mut self, dest: InputDestination) {
+        match dest {
+            InputDestination::Search => {
+                let pattern = self.input.text().to_string();
+                let origin = self.search_origin;
+                let list = self.selected_list();
+                list.current_index = origin;
+                list.jump_to_match(&pattern, true, true);
+                self.search = Some(pattern).filter(|s| !s.is_empty());
+            }
+            InputDestination::Filter => self.set_filter(Some(self.input.text().to_string())),
+            _ => {}
+        }
+    }
+}
+
+/// When the interactive view saves changes, besides when quitting
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Autosave {
+    Off,
+    /// after every change
+    OnChange,
+    /// after a change once this long has passed since the last save
+    Interval(Duration),
+}
+
+impl FromStr for Autosave {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Autosave::Off),
+            "change" => Ok(Autosave::OnChange),
+            _ => match s.parse::<u64>() {
+                Ok(0) => Ok(Autosave::OnChange),
+                Ok(secs) => Ok(Autosave::Interval(Duration::from_secs(secs))),
+                Err(_) => Err(format!("unknown autosave setting {}, expected off, change or a number of seconds", s)),
+            },
+        }
+    }
+}
+
+/// set up the signals that should save and exit the interactive view instead of killing it
+/// the returned value is set to the number of the signal that was received
+#[cfg(unix)]
+fn register_signals() -> io::Result<Arc<AtomicUsize>> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    let signal = Arc::new(AtomicUsize::new(0));
+    for sig in [SIGTERM, SIGHUP, SIGINT] {
+        signal_hook::flag::register_usize(sig, Arc::clone(&signal), sig as usize)?;
+    }
+    Ok(signal)
+}
+
+#[cfg(not(unix))]
+fn register_signals() -> io::Result<Arc<AtomicUsize>> {
+    Ok(Arc::new(AtomicUsize::new(0)))
 }
 
 /// A program that acts as a todo list
 #[derive(Debug, StructOpt)]
 #[structopt(
     name = "todo",
-    usage = "todo [options]",
+    usage = "todo [options] [subcommand]",
     about = r#"A todo program that tracks a two list of completed and uncompleted items
-    environment variables TODO_LIST and TODO_DONE_LIST must be paths to text files to be used
+    list files are taken from --todo-file and --done-file, then the environment variables
+    TODO_LIST and TODO_DONE_LIST, then default to $XDG_DATA_HOME/todo_rs/{todo,done}.txt
     controls:
         NORMAL MODE:
             q, Esc       ->  Quit
@@ -695,22 +2520,97 @@ impl TodoApp {
             E            ->  Edit an item at the beginning
             a, i         ->  Enter insert mode
             h, l         ->  Move from todo to completed
+            Tab          ->  Switch to the next named list
+            Shift-Tab    ->  Switch to the previous named list
+            L            ->  Open the list picker
             g            ->  Move to top of list
             G            ->  Move to bottom of list
             j            ->  Move down on a list
             k            ->  Move up on a list
-            J            ->  Drag an element down on a list
-            K            ->  Drag an element up on a list
-            s            ->  Sort a list
+            /            ->  Search the list, matches are highlighted
+            n, N         ->  Jump to the next or previous match of the search
+            f            ->  Filter the lists to only show items containing some text
+            F            ->  Clear the filter and tag filter
+            t            ->  Open the tag picker to only show items with some tags
+            J            ->  Drag an element and its subtasks down on a list
+            K            ->  Drag an element and its subtasks up on a list
+            >            ->  Make an item a subtask of the item above
+            <            ->  Move a subtask out a level
+            z            ->  Collapse or expand the subtasks of an item
+            s            ->  Open the sort menu
+            D            ->  Sort a list by due date
+            S            ->  Sort a list by priority, then due date, then text
+            !            ->  Change the priority of an item
+            v            ->  Show or hide the details and note of the current item
+            w            ->  Write the note of an item in $EDITOR
+            V            ->  Select a range of items in visual mode
+            m, Space     ->  Mark or unmark an item, checking, deleting and copying then act on every marked item
+            M            ->  Unmark every item
             y            ->  Copy an item in the list
             p            ->  Paste an item in the list after the current item
             P            ->  Paste an item in the list before the current item
+            u            ->  Undo the last change
+            Ctrl-r       ->  Redo the last undone change
+            Ctrl-e       ->  Edit every item of a list in $EDITOR, one per line
             0-9          ->  Set the repitition modifier to a number (doing a command after this will repeat that command
+        LIST PICKER:
+            q, Esc       ->  Close the picker
+            j, k         ->  Move down and up
+            Enter, l     ->  Switch to the selected list
+            a, i         ->  Create a new list
+        TAG PICKER:
+            j, k         ->  Move down and up
+            Space        ->  Pick or unpick a tag
+            Enter        ->  Only show items with one of the picked tags, or the selected one if none are picked
+            c            ->  Show items with any tags again
+            q, Esc       ->  Close the picker
+        SORT MENU:
+            t            ->  Sort by text
+            i            ->  Sort by text ignoring case
+            n            ->  Sort by text with numbers in order
+            l            ->  Sort by length
+            c, x         ->  Sort by creation or completion time
+            d            ->  Sort by due date
+            p            ->  Sort by priority, then due date, then text
+            r            ->  Toggle sorting in reverse
+            s, Enter     ->  Sort the same way as last time
+            q, Esc       ->  Close the menu
+        VISUAL MODE:
+            j, k, g, G   ->  Move the end of the selection
+            y            ->  Copy the selected items
+            d, x, Enter  ->  Move the selected items to completed, or to todo when in completed
+            d            ->  Delete the selected items from completed
+            J, K         ->  Drag the selected items down or up
+            s            ->  Sort the selected items, if they are at the same level
+            V, q, Esc    ->  Go back to normal mode
+        PRIORITY:
+            +, k         ->  Raise the priority
+            -, j         ->  Lower the priority, lowering the lowest priority removes it
+            1-5          ->  Set the priority from A to E
+            0, Backspace ->  Remove the priority
+            Enter, Esc   ->  Go back to normal mode
+        SEARCH AND FILTER:
+            Esc          ->  Clear the search or filter
+            Enter        ->  Keep the search or filter
+            other keys   ->  Edit the search or filter, the lists update as you type
         INSERT MODE:
             Esc          ->  Exit insert mode
             Enter        ->  Add writen todo to list
             Backspace    ->  Remove a character from the label
             Delete       ->  Remove a character but do not move cursor back
+            Home/Ctrl-a  ->  Move to the start of the line
+            End/Ctrl-e   ->  Move to the end of the line
+            Alt-b/Alt-f  ->  Move back/forward a word
+            Ctrl-w       ->  Cut back to the previous space
+            Alt-Bksp     ->  Cut the word before the cursor
+            Alt-d        ->  Cut the word after the cursor
+            Ctrl-u       ->  Cut to the start of the line
+            Ctrl-k       ->  Cut to the end of the line
+            Ctrl-y       ->  Paste the last thing cut
+            Alt-y        ->  Right after pasting, swap it for what was cut before
+            Up, Down     ->  Recall older/newer input starting with what was typed
+            Tab          ->  Complete a word or tag used in the list, again for the next one
+            Shift-Tab    ->  Complete with the previous word
             other keys   ->  write label for todo"#
 )]
 struct Args {
@@ -729,11 +2629,95 @@ struct Args {
     /// Print done list instead of interactive prompt
     #[structopt(short = "d", long)]
     print_done: bool,
+
+    /// Edit the todo list in $EDITOR instead of the interactive prompt, one item per line
+    #[structopt(short, long)]
+    edit: bool,
+
+    /// Only print items with this tag, such as @backend or #release. Can be given more than once
+    #[structopt(short, long, number_of_values = 1)]
+    tag: Vec<String>,
+
+    /// Print the number of each item, which the subcommands take to pick an item
+    #[structopt(short, long)]
+    numbered: bool,
+
+    /// Format to print items in: plain, or json, csv or tsv with each item's number and metadata
+    #[structopt(long, default_value = "plain")]
+    format: OutputFormat,
+
+    /// Path of the todo list file
+    #[structopt(long, env = "TODO_LIST", parse(from_os_str))]
+    todo_file: Option<PathBuf>,
+
+    /// Path of the done list file
+    #[structopt(long, env = "TODO_DONE_LIST", parse(from_os_str))]
+    done_file: Option<PathBuf>,
+
+    /// Use the named list instead of the default one
+    #[structopt(short, long)]
+    list: Option<String>,
+
+    /// Directory holding the named lists
+    #[structopt(long, env = "TODO_LISTS_DIR", parse(from_os_str))]
+    lists_dir: Option<PathBuf>,
+
+    /// Format lists are saved in, either native or todotxt. Files in the native format are always readable
+    #[structopt(long, env = "TODO_STORAGE", default_value = "native")]
+    storage: StorageFormat,
+
+    /// When to save changes in the interactive view: off (only when quitting), change, or a number of seconds
+    #[structopt(long, env = "TODO_AUTOSAVE", default_value = "change")]
+    autosave: Autosave,
+
+    /// Where y copies to and p and P paste from: internal, or system to also use the system clipboard
+    #[structopt(long, env = "TODO_CLIPBOARD", default_value = "internal")]
+    clipboard: ClipboardProvider,
+
+    /// Shell command that copies its input to the system clipboard, such as `wl-copy`, used instead of OSC 52
+    #[structopt(long, env = "TODO_COPY_CMD")]
+    copy_command: Option<String>,
+
+    /// Shell command that prints the system clipboard, such as `wl-paste`. Terminals don't let OSC 52 be read,
+    /// so without one p and P paste what todo last copied
+    #[structopt(long, env = "TODO_PASTE_CMD")]
+    paste_command: Option<String>,
+
+    /// File that what is typed in insert mode is remembered in, for recalling with Up and Down
+    #[structopt(long, env = "TODO_HISTORY", parse(from_os_str))]
+    history_file: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
-fn main() -> crossterm::Result<()> {
+fn main() {
+    if let Err(err) = try_main() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn try_main() -> crossterm::Result<()> {
     let mut stdin = io::stdin();
     let args = Args::from_args();
+    if let Some(name) = &args.list {
+        if !valid_list_name(name) {
+            eprintln!("Invalid list name: {}", name);
+            std::process::exit(1);
+        }
+    }
+    let paths = list_paths(&args, args.list.as_deref());
+    if let Some(command) = &args.command {
+        let name = args.list.clone().unwrap_or_else(|| DEFAULT_LIST_NAME.to_string());
+        let mut named_list = NamedList::load(name, paths, args.storage)?;
+        if let Err(err) = command.run(&mut named_list) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        named_list.save()?;
+        return Ok(());
+    }
     let stdin_tty = stdin.is_tty();
     let stdout_tty = io::stdout().is_tty();
     // interactive
@@ -741,31 +2725,305 @@ fn main() -> crossterm::Result<()> {
         && !args.add_stdin
         && !args.print
         && !args.print_done
+        && !args.edit
         && stdin_tty
         && stdout_tty
     {
+        let signal = register_signals()?;
+        // load the lists before taking over the terminal so errors reading them are shown normally
+        let mut app = TodoApp::new(&args, Arc::clone(&signal))?;
         terminal::enable_raw_mode()?;
-        TodoApp::new().run()?;
-        terminal::disable_raw_mode()?;
+        let res = panic::catch_unwind(AssertUnwindSafe(|| app.run()));
+        match res {
+            Ok(res) => {
+                terminal::disable_raw_mode()?;
+                res?;
+                match signal.load(Ordering::Relaxed) {
+                    0 => {}
+                    sig => std::process::exit(128 + sig as i32),
+                }
+            }
+            Err(err) => {
+                // keep the session's changes even if something went wrong
+                let _ = app.save();
+                let _ = terminal::disable_raw_mode();
+                panic::resume_unwind(err);
+            }
+        }
+    } else if args.edit {
+        edit_list_file(&paths.todo, args.storage)?;
     } else {
         if args.add.is_some() || args.add_stdin || !stdin_tty {
-            let mut list = load_list(TODO_LIST);
-            if let Some(val) = args.add {
-                list.push(val);
+            let mut new_items = vec![];
+            if let Some(val) = &args.add {
+                new_items.push(TodoItem::new(val.clone()));
             } else {
                 let mut val = "".to_string();
                 stdin.read_to_string(&mut val)?;
                 for line in val.split('\n').filter(|x| !x.is_empty()) {
-                    list.push(line.trim().to_string());
+                    new_items.push(TodoItem::new(line.trim().to_string()));
                 }
             };
-            save_list(TODO_LIST, &list);
+            storage::update_list(&paths.todo, args.storage, |list| list.append(&mut new_items))?;
         }
         if args.print_done {
-            print_done();
+            print_list(&load_list(&paths.done, args.storage)?, true, &args);
         } else if args.print || !stdout_tty {
-            print_todo();
+            print_list(&load_list(&paths.todo, args.storage)?, false, &args);
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a todo list of items given by their text and depth
+    fn list_of(items: &[(&str, usize)]) -> List {
+        let items = items
+            .iter()
+            .map(|&(text, depth)| TodoItem { depth, ..TodoItem::from_text(text.to_string()) })
+            .collect();
+        List::new(items, ListType::Todo)
+    }
+
+    fn outline(list: &List) -> Vec<(&str, usize)> {
+        list.items.iter().map(|item| (item.text.as_str(), item.depth)).collect()
+    }
+
+    fn texts(items: &[TodoItem]) -> Vec<&str> {
+        items.iter().map(|item| item.text.as_str()).collect()
+    }
+
+    /// a list with the cursor on the item with `text`
+    fn at(mut list: List, text: &str) -> List {
+        list.current_index = list.items.iter().position(|item| item.text == text).unwrap();
+        list
+    }
+
+    fn collapse(mut list: List, text: &str) -> List {
+        list.items.iter_mut().find(|item| item.text == text).unwrap().collapsed = true;
+        list
+    }
+
+    const TREE: &[(&str, usize)] = &[("a", 0), ("a1", 1), ("a2", 1), ("a2x", 2), ("b", 0), ("b1", 1), ("c", 0)];
+
+    #[test]
+    fn shifting_moves_subtrees_past_siblings() {
+        let mut list = at(list_of(TREE), "a");
+        list.shift_down();
+        assert_eq!(outline(&list), [("b", 0), ("b1", 1), ("a", 0), ("a1", 1), ("a2", 1), ("a2x", 2), ("c", 0)]);
+        assert_eq!(list.items[list.current_index].text, "a");
+        list.shift_up();
+        assert_eq!(outline(&list), TREE);
+        assert_eq!(list.current_index, 0);
+
+        let mut list = at(list, "a1");
+        list.shift_down();
+        assert_eq!(outline(&list), [("a", 0), ("a2", 1), ("a2x", 2), ("a1", 1), ("b", 0), ("b1", 1), ("c", 0)]);
+    }
+
+    #[test]
+    fn shifting_stops_at_the_parent() {
+        let mut list = at(list_of(TREE), "a2");
+        list.shift_down();
+        assert_eq!(outline(&list), TREE);
+        assert_eq!(list.items[list.current_index].text, "a2");
+        let mut list = at(list, "a1");
+        list.shift_up();
+        assert_eq!(outline(&list), TREE);
+        // top level items wrap around instead
+        let mut list = at(list, "a");
+        list.shift_up();
+        assert_eq!(outline(&list), [("b", 0), ("b1", 1), ("c", 0), ("a", 0), ("a1", 1), ("a2", 1), ("a2x", 2)]);
+    }
+
+    #[test]
+    fn shifting_past_collapsed_siblings() {
+        let mut list = at(collapse(list_of(TREE), "b"), "a");
+        list.shift_down();
+        assert_eq!(outline(&list)[..3], [("b", 0), ("b1", 1), ("a", 0)]);
+        // a collapsed item takes its hidden subtasks along with it
+        let mut list = at(collapse(list_of(TREE), "a"), "a");
+        list.shift_down();
+        assert_eq!(outline(&list), [("b", 0), ("b1", 1), ("a", 0), ("a1", 1), ("a2", 1), ("a2x", 2), ("c", 0)]);
+    }
+
+    #[test]
+    fn indenting_under_the_previous_sibling() {
+        let mut list = at(list_of(TREE), "b");
+        list.indent();
+        assert_eq!(outline(&list)[4..], [("b", 1), ("b1", 2), ("c", 0)]);
+        // then under the last subtask of that sibling
+        list.indent();
+        assert_eq!(outline(&list)[3..], [("a2x", 2), ("b", 2), ("b1", 3), ("c", 0)]);
+        // first items have no sibling above to go under
+        for text in ["a", "a1"] {
+            let mut list = at(list_of(TREE), text);
+            list.indent();
+            assert_eq!(outline(&list), TREE);
+        }
+
+        // indenting into a collapsed item expands it so the item stays in sight
+        let mut list = at(collapse(list, "a"), "c");
+        list.indent();
+        assert!(!list.items[0].collapsed);
+        assert!(list.has_current());
+    }
+
+    #[test]
+    fn outdenting_takes_the_subtasks_along() {
+        let mut list = at(list_of(TREE), "a2");
+        list.outdent();
+        assert_eq!(outline(&list)[..4], [("a", 0), ("a1", 1), ("a2", 0), ("a2x", 1)]);
+        list.outdent();
+        assert_eq!(outline(&list)[..4], [("a", 0), ("a1", 1), ("a2", 0), ("a2x", 1)]);
+    }
+
+    #[test]
+    fn selections_and_marks_act_on_roots() {
+        let mut list = at(list_of(TREE), "a2x");
+        list.anchor = Some(1);
+        assert_eq!(list.selected_roots(), [1, 2]);
+        assert_eq!(list.selection(), Some(1..4));
+        list.current_index = 5;
+        assert_eq!(list.selected_roots(), [1, 2, 4]);
+        assert_eq!(list.level_selection(), None);
+
+        // hidden subtasks of a collapsed root come along with it
+        let mut list = at(collapse(list, "a"), "b");
+        list.anchor = Some(0);
+        assert_eq!(list.selected_roots(), [0, 4]);
+        assert_eq!(list.selection(), Some(0..6));
+
+        list.marked = BTreeSet::from([0, 1, 5, 6]);
+        assert_eq!(list.marked_roots(), [0, 5, 6]);
+        assert_eq!(list.clone_marked().len(), 6);
+    }
+
+    #[test]
+    fn acting_on_each_root_keeps_later_roots_in_place() {
+        let dir = std::env::temp_dir().join(format!("todo_rs-test-{}-each_root", std::process::id()));
+        let paths = ListPaths { todo: dir.join("todo.txt"), done: dir.join("done.txt") };
+        let mut named_list = NamedList::load("test".to_string(), paths, StorageFormat::Native).unwrap();
+        named_list.todo = collapse(list_of(TREE), "a");
+        named_list.for_each_root(ListType::Todo, &[0, 6], NamedList::check_item);
+        assert_eq!(outline(&named_list.todo), [("b", 0), ("b1", 1)]);
+        assert_eq!(texts(&named_list.done.items), ["a", "a1", "a2", "a2x", "c"]);
+        assert!(named_list.done.items.iter().all(|item| item.completed.is_some()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn matching_ignores_case_outside_ascii() {
+        assert!(matches("Café ÉCLAIR", "éclair"));
+        assert!(matches("ΟΔΟΣ", "οδοσ"));
+        assert!(!matches("cafe", "é"));
+        assert_eq!(find_matches("CAFÉ café", "É"), [3..5, 9..11]);
+        assert_eq!(find_matches("Ab aB", "ab"), [0..2, 3..5]);
+        // İ lowercases to two characters, a match on part of it highlights all of it
+        let text = "İstanbul";
+        let found = find_matches(text, "i");
+        assert_eq!(found.len(), 1);
+        assert_eq!(&text[found[0].clone()], "İ");
+        // the kelvin sign takes three bytes but lowercases to a one byte k
+        assert_eq!(find_matches("Kelvin \u{212A}", "k"), [0..1, 7..10]);
+        assert_eq!(find_matches("abc", ""), []);
+    }
+
+    #[test]
+    fn printed_numbers_skip_filtered_items() {
+        let items: Vec<TodoItem> = ["a +x", "b", "c +x", "d #x"]
+            .iter()
+            .map(|text| TodoItem::from_text(text.to_string()))
+            .collect();
+        let numbers = |tags: &[&str]| -> Vec<usize> {
+            let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+            list_entries(&items, false, "default", &tags).iter().map(|entry| entry.index).collect()
+        };
+        assert_eq!(numbers(&[]), [1, 2, 3, 4]);
+        assert_eq!(numbers(&["+x"]), [1, 3]);
+        assert_eq!(numbers(&["x"]), [1, 3, 4]);
+        assert_eq!(numbers(&["nothing"]), []);
+    }
+
+    #[test]
+    fn sorting_a_selection_keeps_items_under_their_parents() {
+        let reverse = Sort { key: SortKey::Text, reverse: true };
+        let mut mixed = list_of(&[("a", 0), ("b", 1), ("c", 0)]);
+        mixed.anchor = Some(1);
+        mixed.current_index = 2;
+        assert_eq!(mixed.level_selection(), None);
+
+        let mut siblings = list_of(&[("a", 0), ("b", 1), ("b1", 2), ("c", 1), ("d", 0)]);
+        siblings.anchor = Some(1);
+        siblings.current_index = 3;
+        let range = siblings.level_selection().unwrap();
+        assert_eq!(range, 1..4);
+        siblings.sort_range(reverse, range);
+        assert_eq!(outline(&siblings), [("a", 0), ("c", 1), ("b", 1), ("b1", 2), ("d", 0)]);
+        // a range left over from before items were removed is cut short
+        siblings.items.truncate(2);
+        siblings.sort_range(reverse, 1..4);
+        assert_eq!(outline(&siblings), [("a", 0), ("c", 1)]);
+    }
+
+    #[test]
+    fn word_wrap_ascii() {
+        assert_eq!(word_wrap("hello world", 20), vec!["hello world"]);
+        assert_eq!(word_wrap("hello world", 8), vec!["hello", "world"]);
+        assert_eq!(word_wrap("abcdefghij", 6), vec!["abcde", "fghij"]);
+    }
+
+    #[test]
+    fn word_wrap_accents_do_not_panic() {
+        assert_eq!(word_wrap("café crème brûlée", 12), vec!["café crème", "brûlée"]);
+
+        // e followed by a combining acute accent is a single column
+        assert_eq!(word_wrap("e\u{301}e\u{301}e\u{301}e\u{301}", 3), vec!["e\u{301}e\u{301}", "e\u{301}e\u{301}"]);
+    }
+
+    #[test]
+    fn word_wrap_uses_display_width() {
+        // each of these characters takes up two columns
+        assert_eq!(word_wrap("日本語のテキスト", 7), vec!["日本語", "のテキ", "スト"]);
+        assert_eq!(word_wrap("🎉🎉🎉 party", 8), vec!["🎉🎉🎉", "party"]);
+        for line in word_wrap("漢字 かな カナ 漢字かなカナ", 9) {
+            assert!(line.width() < 9, "{} is too wide", line);
+        }
+    }
+
+    #[test]
+    fn word_wrap_grapheme_wider_than_line() {
+        assert_eq!(word_wrap("日本", 2), vec!["日", "本"]);
+    }
+
+    #[test]
+    fn grapheme_cursor_movement() {
+        let s = "ae\u{301}🎉x";
+        let mut index = 0;
+        let mut stops = vec![index];
+        while index < s.len() {
+            index = next_grapheme(s, index);
+            stops.push(index);
+        }
+        assert_eq!(stops, vec![0, 1, 4, 8, 9]);
+        while index > 0 {
+            index = prev_grapheme(s, index);
+            stops.pop();
+            assert_eq!(Some(&index), stops.last());
+        }
+        assert_eq!(next_grapheme(s, s.len()), s.len());
+        assert_eq!(prev_grapheme(s, 0), 0);
+    }
+
+    #[test]
+    fn grapheme_cursor_with_family_emoji() {
+        // a single grapheme made of several code points joined with zero width joiners
+        let family = "👨\u{200d}👩\u{200d}👧";
+        let s = format!("a{}b", family);
+        assert_eq!(next_grapheme(&s, 1), 1 + family.len());
+        assert_eq!(prev_grapheme(&s, 1 + family.len()), 1);
+    }
+}
@@ -0,0 +1,218 @@
+use std::str::FromStr;
+use crate::item::TodoItem;
+
+/// How `--print` and `--print-done` write items
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// one item per line with subtasks indented
+    Plain,
+    /// an array of objects with every field of the items
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(OutputFormat::Plain),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => Err(format!("unknown format {}, expected plain, json, csv or tsv", s)),
+        }
+    }
+}
+
+/// The fields written for each item by the json, csv and tsv formats
+const FIELDS: [&str; 11] = ["index", "list", "done", "text", "depth", "priority", "created", "completed", "due", "tags", "note"];
+
+/// An item as it is printed, along with where it is
+pub struct Entry<'a> {
+    /// number of the item counting from 1, as the subcommands take it
+    pub index: usize,
+    pub list: &'a str,
+    pub done: bool,
+    pub item: &'a TodoItem,
+}
+
+impl Entry<'_> {
+    /// the values of `FIELDS`, or None for ones the item doesn't have
+    fn values(&self) -> Vec<Option<String>> {
+        vec![
+            Some(self.index.to_string()),
+            Some(self.list.to_string()),
+            Some(self.done.to_string()),
+            Some(self.item.text.clone()),
+            Some(self.item.depth.to_string()),
+            self.item.priority.map(String::from),
+            self.item.created.map(|created| created.to_string()),
+            self.item.completed.map(|completed| completed.to_string()),
+            self.item.due().map(|due| due.to_string()),
+            Some(self.item.tags().join(" ")),
+            self.item.note.clone(),
+        ]
+    }
+
+    fn to_json(&self) -> String {
+        let tags: Vec<String> = self.item.tags().iter().map(|tag| json_string(tag)).collect();
+        let fields: Vec<String> = FIELDS
+            .iter()
+            .zip(self.values())
+            .filter_map(|(field, value)| {
+                let value = match *field {
+                    "index" | "done" | "depth" | "created" | "completed" => value?,
+                    "tags" => format!("[{}]", tags.join(",")),
+                    _ => json_string(&value?),
+                };
+                Some(format!("\"{}\":{}", field, value))
+            })
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+/// write entries in `format`, numbering the lines of the plain format if `numbered` is true
+pub fn format_entries(entries: &[Entry], format: OutputFormat, numbered: bool) -> String {
+    let mut res = String::new();
+    match format {
+        OutputFormat::Plain => {
+            let width = entries.last().map_or(1, |entry| entry.index.to_string().len());
+            for entry in entries {
+                if numbered {
+                    res.push_str(&format!("{:>width$} ", entry.index, width = width));
+                }
+                res.push_str(&" ".repeat(entry.item.depth * crate::INDENT_WIDTH));
+                res.push_str(&entry.item.text);
+                res.push('\n');
+            }
+        }
+        OutputFormat::Json => {
+            let objects: Vec<String> = entries.iter().map(Entry::to_json).collect();
+            if objects.is_empty() {
+                res.push_str("[]\n");
+            } else {
+                res.push_str(&format!("[\n{}\n]\n", objects.join(",\n")));
+            }
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let (separator, escape): (&str, fn(&str) -> String) = if format == OutputFormat::Csv {
+                (",", csv_field)
+            } else {
+                ("\t", tsv_field)
+            };
+            res.push_str(&FIELDS.join(separator));
+            res.push('\n');
+            for entry in entries {
+                let values: Vec<String> = entry
+                    .values()
+                    .iter()
+                    .map(|value| value.as_deref().map(escape).unwrap_or_default())
+                    .collect();
+                res.push_str(&values.join(separator));
+                res.push('\n');
+            }
+        }
+    }
+    res
+}
+
+fn json_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => res.push(ch),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// quote a field if it has anything special in csv, doubling any quotes in it
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// tsv has no quoting so tabs and line breaks are escaped like in the list files
+fn tsv_field(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AWKWARD: &str = "say \"hi\", then\ttab\nnew line\r\u{1}é 日本";
+
+    fn entry(index: usize, item: &TodoItem) -> Entry<'_> {
+        Entry { index, list: "default", done: false, item }
+    }
+
+    #[test]
+    fn json_escaping() {
+        assert_eq!(json_string(AWKWARD), "\"say \\\"hi\\\", then\\ttab\\nnew line\\r\\u0001é 日本\"");
+        assert_eq!(json_string("back\\slash"), "\"back\\\\slash\"");
+        let item = TodoItem { note: Some("a\nb".to_string()), ..TodoItem::from_text("x +proj \"q\"".to_string()) };
+        let json = format_entries(&[entry(1, &item)], OutputFormat::Json, false);
+        assert_eq!(
+            json,
+            "[\n{\"index\":1,\"list\":\"default\",\"done\":false,\"text\":\"x +proj \\\"q\\\"\",\"depth\":0,\
+             \"tags\":[\"+proj\"],\"note\":\"a\\nb\"}\n]\n",
+        );
+        assert_eq!(format_entries(&[], OutputFormat::Json, false), "[]\n");
+    }
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(csv_field("plain é 日本"), "plain é 日本");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+        // tabs and other control characters don't need quoting in csv
+        assert_eq!(csv_field("tab\there\u{1}"), "tab\there\u{1}");
+    }
+
+    #[test]
+    fn tsv_escaping() {
+        assert_eq!(tsv_field(AWKWARD), "say \"hi\", then\\ttab\\nnew line\\r\u{1}é 日本");
+        assert_eq!(tsv_field("back\\slash\\t"), "back\\\\slash\\\\t");
+        let item = TodoItem::from_text("a\tb".to_string());
+        let tsv = format_entries(&[entry(1, &item)], OutputFormat::Tsv, false);
+        let lines: Vec<&str> = tsv.lines().collect();
+        assert_eq!(lines[0].split('\t').count(), FIELDS.len());
+        assert_eq!(lines[1].split('\t').count(), FIELDS.len());
+        assert_eq!(lines[1].split('\t').nth(3), Some("a\\tb"));
+    }
+
+    #[test]
+    fn numbers_are_the_item_positions() {
+        // items left out by a tag filter keep the numbers of the others as they are in the list
+        let items: Vec<TodoItem> = ["a", "b", "c"]
+            .iter()
+            .map(|text| TodoItem::from_text(text.to_string()))
+            .collect();
+        let mut subtask = TodoItem::from_text("d".to_string());
+        subtask.depth = 1;
+        let entries = [entry(1, &items[0]), entry(3, &items[2]), entry(10, &subtask)];
+        let indent = " ".repeat(crate::INDENT_WIDTH);
+        assert_eq!(format_entries(&entries, OutputFormat::Plain, true), format!(" 1 a\n 3 c\n10 {}d\n", indent));
+        assert_eq!(format_entries(&entries, OutputFormat::Plain, false), format!("a\nc\n{}d\n", indent));
+        let csv = format_entries(&entries, OutputFormat::Csv, false);
+        let numbers: Vec<&str> = csv.lines().skip(1).map(|line| line.split(',').next().unwrap()).collect();
+        assert_eq!(numbers, ["1", "3", "10"]);
+    }
+}
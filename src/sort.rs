@@ -0,0 +1,306 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::item::TodoItem;
+use crate::storage::{write_atomic, ListLock};
+
+/// What a list can be sorted by
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SortKey {
+    /// text, with uppercase before lowercase
+    Text,
+    IgnoreCase,
+    /// text with runs of digits compared by their value, so `item 2` comes before `item 10`
+    Natural,
+    Length,
+    Created,
+    Completed,
+    Due,
+    /// priority, then due date, then text
+    Priority,
+}
+
+impl SortKey {
+    /// every key along with the key that picks it in the sort menu
+    pub const ALL: [(char, SortKey); 8] = [
+        ('t', SortKey::Text),
+        ('i', SortKey::IgnoreCase),
+        ('n', SortKey::Natural),
+        ('l', SortKey::Length),
+        ('c', SortKey::Created),
+        ('x', SortKey::Completed),
+        ('d', SortKey::Due),
+        ('p', SortKey::Priority),
+    ];
+
+    pub fn from_char(ch: char) -> Option<Self> {
+        Self::ALL.iter().find(|(key, _)| *key == ch).map(|(_, sort_key)| *sort_key)
+    }
+
+    /// short name used on the command line
+    pub fn id(self) -> &'static str {
+        match self {
+            SortKey::Text => "text",
+            SortKey::IgnoreCase => "ignorecase",
+            SortKey::Natural => "natural",
+            SortKey::Length => "length",
+            SortKey::Created => "created",
+            SortKey::Completed => "completed",
+            SortKey::Due => "due",
+            SortKey::Priority => "priority",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Text => "text",
+            SortKey::IgnoreCase => "text ignoring case",
+            SortKey::Natural => "text with numbers in order",
+            SortKey::Length => "length",
+            SortKey::Created => "creation time",
+            SortKey::Completed => "completion time",
+            SortKey::Due => "due date",
+            SortKey::Priority => "priority, due date and text",
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .map(|(_, key)| *key)
+            .find(|key| key.id() == s)
+            .ok_or_else(|| {
+                let ids: Vec<&str> = Self::ALL.iter().map(|(_, key)| key.id()).collect();
+                format!("unknown sort {}, expected one of {}", s, ids.join(", "))
+            })
+    }
+}
+
+/// A way of sorting a list
+/// sorts are stable so sorting by one key and then another orders by both
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sort {
+    pub key: SortKey,
+    pub reverse: bool,
+}
+
+impl Sort {
+    pub fn new(key: SortKey) -> Self {
+        Self { key, reverse: false }
+    }
+
+    /// compare two items, items missing whatever is sorted by always go last
+    pub fn compare(self, a: &TodoItem, b: &TodoItem) -> Ordering {
+        let ord = |ordering: Ordering| if self.reverse { ordering.reverse() } else { ordering };
+        match self.key {
+            SortKey::Text => ord(a.text.cmp(&b.text)),
+            SortKey::IgnoreCase => ord(a.text.to_lowercase().cmp(&b.text.to_lowercase())),
+            SortKey::Natural => ord(natural_cmp(&a.text, &b.text)),
+            SortKey::Length => ord(a.text.chars().count().cmp(&b.text.chars().count())),
+            SortKey::Created => missing_last(a.created, b.created, ord),
+            SortKey::Completed => missing_last(a.completed, b.completed, ord),
+            SortKey::Due => missing_last(a.due(), b.due(), ord),
+            SortKey::Priority => missing_last(a.priority, b.priority, ord)
+                .then_with(|| missing_last(a.due(), b.due(), ord))
+                .then_with(|| ord(a.text.cmp(&b.text))),
+        }
+    }
+
+    /// sort items, keeping subtasks under their parent and sorting them among themselves
+    pub fn apply(self, items: Vec<TodoItem>) -> Vec<TodoItem> {
+        // each group is an item followed by its subtasks
+        let mut groups: Vec<(TodoItem, Vec<TodoItem>)> = vec![];
+        for item in items {
+            match groups.last_mut() {
+                Some((parent, children)) if item.depth > parent.depth => children.push(item),
+                _ => groups.push((item, vec![])),
+            }
+        }
+        groups.sort_by(|a, b| self.compare(&a.0, &b.0));
+        groups
+            .into_iter()
+            .flat_map(|(parent, children)| std::iter::once(parent).chain(self.apply(children)))
+            .collect()
+    }
+}
+
+/// the sort `list` last had, as saved in the file at `path` by `save_last_sorts`
+pub fn load_last_sort(path: &Path, list: &Path) -> Option<Sort> {
+    let list = std::path::absolute(list).ok()?;
+    read_last_sorts(path).into_iter().find(|(path, _)| *path == list).map(|(_, sort)| sort)
+}
+
+/// save how each of `lists` was last sorted, forgetting the sorts of the ones given None
+/// the file is read again first so sorts saved by other instances of todo are kept
+pub fn save_last_sorts(path: &Path, lists: &[(&Path, Option<Sort>)]) -> io::Result<()> {
+    let _lock = ListLock::acquire(path)?;
+    let mut sorts = read_last_sorts(path);
+    for (list, sort) in lists {
+        let list = std::path::absolute(list)?;
+        sorts.retain(|(path, _)| *path != list);
+        if let Some(sort) = sort {
+            sorts.push((list, *sort));
+        }
+    }
+    // the path goes last since it could have tabs in it
+    let contents: String = sorts
+        .iter()
+        .map(|(list, sort)| {
+            let direction = if sort.reverse { "reverse" } else { "forward" };
+            format!("{}\t{}\t{}\n", sort.key.id(), direction, list.display())
+        })
+        .collect();
+    write_atomic(path, contents.as_bytes())
+}
+
+fn read_last_sorts(path: &Path) -> Vec<(PathBuf, Sort)> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let key = fields.next()?.parse().ok()?;
+            let reverse = fields.next()? == "reverse";
+            Some((PathBuf::from(fields.next()?), Sort { key, reverse }))
+        })
+        .collect()
+}
+
+/// compare optional values with `ord`, putting missing values after present ones
+fn missing_last<T: Ord>(a: Option<T>, b: Option<T>, ord: impl Fn(Ordering) -> Ordering) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => ord(a.cmp(&b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// case insensitive comparison where runs of digits are compared by their numeric value
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                // compare by length first so numbers of any size work, leading zeros are ignored
+                let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// take a run of digits from the start of `chars` without its leading zeros
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut number = String::new();
+    while let Some(ch) = chars.next_if(char::is_ascii_digit) {
+        if !(number.is_empty() && ch == '0') {
+            number.push(ch);
+        }
+    }
+    number
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(texts: &[&str]) -> Vec<TodoItem> {
+        texts.iter().map(|text| TodoItem::from_text(text.to_string())).collect()
+    }
+
+    fn texts(items: &[TodoItem]) -> Vec<&str> {
+        items.iter().map(|item| item.text.as_str()).collect()
+    }
+
+    #[test]
+    fn natural_order() {
+        let mut words = vec!["item 10", "Item 2", "item 1", "item 02a", "item", "item 002", "x9", "x10"];
+        words.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(words, ["item", "item 1", "Item 2", "item 002", "item 02a", "item 10", "x9", "x10"]);
+        assert_eq!(natural_cmp("007", "7"), Ordering::Equal);
+        assert_eq!(natural_cmp("a0", "a"), Ordering::Greater);
+        // numbers too long for any integer type
+        assert_eq!(natural_cmp("v99999999999999999999999999", "v100000000000000000000000000"), Ordering::Less);
+        assert_eq!(natural_cmp("v000000000000000000000000001", "v2"), Ordering::Less);
+    }
+
+    #[test]
+    fn keys() {
+        let list = items(&["bb", "a", "B", "ccc"]);
+        let sorted = |key| texts(&Sort::new(key).apply(list.clone())).join(" ");
+        assert_eq!(sorted(SortKey::Text), "B a bb ccc");
+        assert_eq!(sorted(SortKey::IgnoreCase), "a B bb ccc");
+        assert_eq!(sorted(SortKey::Length), "a B bb ccc");
+    }
+
+    #[test]
+    fn missing_values_go_last_even_reversed() {
+        let mut list = items(&["none", "c", "a due:2024-01-02", "b due:2024-01-01"]);
+        list[1].priority = Some('C');
+        list[2].priority = Some('A');
+        list[3].priority = Some('A');
+        assert_eq!(texts(&Sort::new(SortKey::Priority).apply(list.clone())), ["b due:2024-01-01", "a due:2024-01-02", "c", "none"]);
+        let reverse = Sort { key: SortKey::Due, reverse: true };
+        assert_eq!(texts(&reverse.apply(list)), ["a due:2024-01-02", "b due:2024-01-01", "none", "c"]);
+    }
+
+    #[test]
+    fn reverse_sorts_are_stable() {
+        // reversing flips the order of the key but items with equal keys keep their order
+        let reverse = Sort { key: SortKey::Length, reverse: true };
+        assert_eq!(texts(&reverse.apply(items(&["aa", "b", "cc", "d"]))), ["aa", "cc", "b", "d"]);
+        // so sorting by one key and then another orders by both
+        let by_name = Sort { key: SortKey::Natural, reverse: true }.apply(items(&["b 1", "a 22", "b 22", "a 1"]));
+        assert_eq!(texts(&by_name), ["b 22", "b 1", "a 22", "a 1"]);
+        assert_eq!(texts(&reverse.apply(by_name)), ["b 22", "a 22", "b 1", "a 1"]);
+    }
+
+    #[test]
+    fn last_sorts_are_saved() {
+        let dir = std::env::temp_dir().join(format!("todo_rs-test-{}-sorts", std::process::id()));
+        let path = dir.join("sorts.txt");
+        let (todo, done) = (dir.join("todo\twith tab.txt"), dir.join("done.txt"));
+        let natural = Sort { key: SortKey::Natural, reverse: true };
+        save_last_sorts(&path, &[(&todo, Some(natural)), (&done, Some(Sort::new(SortKey::Due)))]).unwrap();
+        assert_eq!(load_last_sort(&path, &todo), Some(natural));
+        assert_eq!(load_last_sort(&path, &done), Some(Sort::new(SortKey::Due)));
+        save_last_sorts(&path, &[(&done, None)]).unwrap();
+        assert_eq!(load_last_sort(&path, &todo), Some(natural));
+        assert_eq!(load_last_sort(&path, &done), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn subtasks_stay_under_their_parent() {
+        let mut list = items(&["b", "b2", "b1", "a", "a1"]);
+        for index in [1, 2, 4] {
+            list[index].depth = 1;
+        }
+        let sorted = Sort::new(SortKey::Text).apply(list);
+        let outline: Vec<(&str, usize)> = sorted.iter().map(|item| (item.text.as_str(), item.depth)).collect();
+        assert_eq!(outline, [("a", 0), ("a1", 1), ("b", 0), ("b1", 1), ("b2", 1)]);
+    }
+}
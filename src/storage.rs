@@ -0,0 +1,336 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::item::{self, StorageFormat, TodoItem};
+
+/// Identifies a version of a file on disk so changes made by other processes can be noticed cheaply
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    /// stamp of the file at `path`, or None if it doesn't exist
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// An advisory lock on a list file, released when dropped
+/// the lock is taken on a separate hidden `.<name>.lock` file next to the list since the list file itself
+/// gets replaced when saving. it is left in place afterwards, removing it could let two processes
+/// lock different files for the same list
+pub struct ListLock {
+    _file: File,
+}
+
+impl ListLock {
+    pub fn acquire(path: &Path) -> io::Result<Self> {
+        let path = resolve_symlinks(path);
+        create_parent_dir(&path)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(sibling_path(&path, ".", ".lock"))?;
+        file.lock()?;
+        Ok(Self { _file: file })
+    }
+}
+
+fn create_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::create_dir_all(dir),
+        _ => Ok(()),
+    }
+}
+
+/// a path in the same directory as `path` with a prefix and suffix added to its file name
+fn sibling_path(path: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let mut name = std::ffi::OsString::from(prefix);
+    name.push(path.file_name().unwrap_or_default());
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// the file a chain of symbolic links ends at, or `path` itself if it isn't a link
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    // give up on links that go around in a loop
+    for _ in 0..40 {
+        match fs::read_link(&path) {
+            Ok(target) => path = path.parent().map_or_else(|| target.clone(), |dir| dir.join(&target)),
+            Err(_) => break,
+        }
+    }
+    path
+}
+
+/// replace the contents of a file so that it is never left partially written
+/// the contents are written to a temporary file that is then renamed over the original.
+/// symbolic links are followed so the file they point at is replaced rather than the link,
+/// and the new file gets the permissions of the old one
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let path = resolve_symlinks(path);
+    create_parent_dir(&path)?;
+    let tmp_path = sibling_path(&path, ".", &format!(".tmp{}", std::process::id()));
+    let res = (|| {
+        let mut file = File::create(&tmp_path)?;
+        if let Ok(metadata) = fs::metadata(&path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)
+    })();
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res
+}
+
+/// load a list file saved in `format`, see `item::parse_items`
+/// a missing file is an empty list, but files that can't be read or understood are errors
+/// so they are never overwritten
+pub fn load_list(filename: &Path, format: StorageFormat) -> io::Result<Vec<TodoItem>> {
+    let contents = match fs::read_to_string(filename) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(io::Error::new(err.kind(), format!("{}: {}", filename.display(), err))),
+    };
+    item::parse_items(&contents, format)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", filename.display(), err)))
+}
+
+pub fn save_list(filename: &Path, list: &[TodoItem], format: StorageFormat) -> io::Result<()> {
+    write_atomic(filename, item::serialize_items(list, format).as_bytes())
+}
+
+/// lock a list file while `f` changes its items, then save them
+pub fn update_list<T>(
+    filename: &Path,
+    format: StorageFormat,
+    f: impl FnOnce(&mut Vec<TodoItem>) -> T,
+) -> io::Result<T> {
+    let _lock = ListLock::acquire(filename)?;
+    let mut items = load_list(filename, format)?;
+    let res = f(&mut items);
+    save_list(filename, &items, format)?;
+    Ok(res)
+}
+
+/// remove the first item equal to `item` from `items`, returning true if one was found
+fn remove_first(items: &mut Vec<TodoItem>, item: &TodoItem) -> bool {
+    match items.iter().position(|x| x == item) {
+        Some(index) => {
+            items.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// three way merge of a list changed both in memory and on disk
+/// `base` is the list as it was last read from or written to disk, `ours` is the list in memory
+/// and `theirs` is the list currently on disk.
+/// items removed on disk are removed from ours and items added on disk are appended to ours
+pub fn merge_items(base: &[TodoItem], ours: &[TodoItem], theirs: &[TodoItem]) -> Vec<TodoItem> {
+    merge_items_by(base, ours, theirs, TodoItem::clone)
+}
+
+/// `merge_items` where `base` and `theirs` were read from disk and `saved` gives an item of `ours`
+/// as it would be read back, for formats that don't keep everything like the exact times in todo.txt
+fn merge_items_by(
+    base: &[TodoItem],
+    ours: &[TodoItem],
+    theirs: &[TodoItem],
+    saved: impl Fn(&TodoItem) -> TodoItem,
+) -> Vec<TodoItem> {
+    let mut added = theirs.to_vec();
+    let mut removed = vec![];
+    for item in base {
+        if !remove_first(&mut added, item) {
+            removed.push(item.clone());
+        }
+    }
+    let mut res: Vec<(TodoItem, TodoItem)> = ours.iter().map(|item| (item.clone(), saved(item))).collect();
+    for item in &removed {
+        if let Some(index) = res.iter().position(|(_, saved)| saved == item) {
+            res.remove(index);
+        }
+    }
+    res.into_iter().map(|(item, _)| item).chain(added).collect()
+}
+
+/// A list file along with its contents when it was last read or written
+/// used to notice and merge in changes made by other processes before overwriting them
+pub struct ListFile {
+    pub path: PathBuf,
+    format: StorageFormat,
+    base: Vec<TodoItem>,
+    stamp: Option<FileStamp>,
+}
+
+impl ListFile {
+    /// read a list file saved in `format`, returning it along with its items
+    pub fn load(path: PathBuf, format: StorageFormat) -> io::Result<(Self, Vec<TodoItem>)> {
+        let _lock = ListLock::acquire(&path).ok();
+        let items = load_list(&path, format)?;
+        let file = Self {
+            format,
+            base: items.clone(),
+            stamp: FileStamp::of(&path),
+            path,
+        };
+        Ok((file, items))
+    }
+
+    /// true if the file has been changed by something else since it was last read or written
+    pub fn changed_on_disk(&self) -> bool {
+        FileStamp::of(&self.path) != self.stamp
+    }
+
+    /// merge changes made on disk into `items` without saving
+    /// returns true if `items` changed
+    pub fn reload(&mut self, items: &mut Vec<TodoItem>) -> io::Result<bool> {
+        if !self.changed_on_disk() {
+            return Ok(false);
+        }
+        let _lock = ListLock::acquire(&self.path).ok();
+        let theirs = load_list(&self.path, self.format)?;
+        self.stamp = FileStamp::of(&self.path);
+        if theirs == self.base {
+            return Ok(false);
+        }
+        let merged = self.merge(items, &theirs);
+        // the in memory changes still need saving so compare them against what's on disk from now on
+        self.base = theirs;
+        let changed = merged != *items;
+        *items = merged;
+        Ok(changed)
+    }
+
+    /// save `items`, first merging in any changes made on disk since the file was last read or written
+    /// returns true if there were changes on disk that were merged in
+    pub fn save(&mut self, items: &mut Vec<TodoItem>) -> io::Result<bool> {
+        let _lock = ListLock::acquire(&self.path)?;
+        let theirs = load_list(&self.path, self.format)?;
+        let merged = theirs != self.base;
+        if merged {
+            *items = self.merge(items, &theirs);
+        }
+        let contents = item::serialize_items(items, self.format);
+        // compare what is read back rather than the items since not every format keeps all of their metadata
+        let saved = item::parse_items(&contents, self.format).unwrap_or_default();
+        if merged || saved != self.base || !self.path.exists() {
+            write_atomic(&self.path, contents.as_bytes())?;
+        }
+        self.base = saved;
+        self.stamp = FileStamp::of(&self.path);
+        Ok(merged)
+    }
+
+    fn merge(&self, ours: &[TodoItem], theirs: &[TodoItem]) -> Vec<TodoItem> {
+        merge_items_by(&self.base, ours, theirs, |item| item.as_saved(self.format))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(texts: &[&str]) -> Vec<TodoItem> {
+        texts.iter().map(|text| TodoItem::from_text(text.to_string())).collect()
+    }
+
+    fn texts(items: &[TodoItem]) -> Vec<&str> {
+        items.iter().map(|item| item.text.as_str()).collect()
+    }
+
+    /// an empty directory for a test to put lists in
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("todo_rs-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn todo_txt_saves_keep_the_order() {
+        let dir = test_dir("todo_txt_saves");
+        let path = dir.join("todo.txt");
+        let (mut file, mut items) = ListFile::load(path.clone(), StorageFormat::TodoTxt).unwrap();
+        // new items have times that todo.txt only keeps the dates of
+        items.extend(["x", "y"].map(|text| TodoItem::new(text.to_string())));
+        file.save(&mut items).unwrap();
+        for text in ["new1", "new2", "new3"] {
+            items.insert(0, TodoItem::new(text.to_string()));
+            assert!(!file.save(&mut items).unwrap());
+        }
+        let expected = ["new3", "new2", "new1", "x", "y"];
+        assert_eq!(texts(&items), expected);
+        assert_eq!(texts(&load_list(&path, StorageFormat::TodoTxt).unwrap()), expected);
+
+        // changes on disk are still merged in
+        update_list(&path, StorageFormat::TodoTxt, |items| {
+            items.retain(|item| item.text != "x");
+            items.push(TodoItem::new("theirs".to_string()));
+        })
+        .unwrap();
+        assert!(file.reload(&mut items).unwrap());
+        assert!(!file.save(&mut items).unwrap());
+        let expected = ["new3", "new2", "new1", "y", "theirs"];
+        assert_eq!(texts(&items), expected);
+        assert_eq!(texts(&load_list(&path, StorageFormat::TodoTxt).unwrap()), expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn merge_keeps_changes_from_both_sides() {
+        let base = items(&["a", "b", "c"]);
+        // b was checked off here while d was added and a removed on disk
+        let ours = items(&["a", "c", "e"]);
+        let theirs = items(&["b", "c", "d"]);
+        assert_eq!(merge_items(&base, &ours, &theirs), items(&["c", "e", "d"]));
+    }
+
+    #[test]
+    fn merge_with_nothing_removed_keeps_our_order() {
+        let base = items(&["a", "b"]);
+        let ours = items(&["b", "a", "c"]);
+        assert_eq!(merge_items(&base, &ours, &base), ours);
+        assert_eq!(merge_items(&base, &base, &items(&["a", "b", "c"])), items(&["a", "b", "c"]));
+    }
+
+    #[test]
+    fn merge_edits_replace_the_old_item() {
+        let base = items(&["a", "b"]);
+        let ours = items(&["a", "b", "new"]);
+        let theirs = items(&["a", "b edited"]);
+        assert_eq!(merge_items(&base, &ours, &theirs), items(&["a", "new", "b edited"]));
+    }
+
+    #[test]
+    fn merge_duplicates_are_counted() {
+        // one of two equal items was removed on disk, and another added here
+        let base = items(&["a", "a"]);
+        let ours = items(&["a", "a", "a"]);
+        let theirs = items(&["a"]);
+        assert_eq!(merge_items(&base, &ours, &theirs), items(&["a", "a"]));
+    }
+
+    #[test]
+    fn merge_metadata_changes_count_as_edits() {
+        let base = items(&["a"]);
+        let mut done = base.clone();
+        done[0].complete();
+        assert_eq!(merge_items(&base, &base, &done), done);
+        assert_eq!(merge_items(&base, &done, &base), done);
+    }
+}